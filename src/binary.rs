@@ -0,0 +1,23 @@
+// ビット列 (先頭が最上位ビット) を数にする
+pub fn pack(bits: &[u8]) -> usize {
+    bits.iter().fold(0, |acc, b| (acc << 1) | *b as usize)
+}
+
+// 数を width ビットのビット列 (先頭が最上位ビット) にする
+pub fn unpack(value: usize, width: usize) -> Vec<u8> {
+    (0..width).map(|i| bit(value, width, i)).collect()
+}
+
+// width ビットの数の先頭から index 番目のビット
+pub fn bit(value: usize, width: usize, index: usize) -> u8 {
+    ((value >> (width - 1 - index)) & 1) as u8
+}
+
+// 0 -> -1, 1 -> 1
+pub fn bpsk(bit: u8) -> f64 {
+    match bit {
+        0 => -1.,
+        1 => 1.,
+        _ => panic!("cant convert {} to bpsk", bit),
+    }
+}
@@ -0,0 +1,246 @@
+// 設定ファイル (TOML / JSON) に書いた実験をまとめて回す
+//
+// 符号 (とそれぞれの復号器)・通信路・SN の範囲・止める条件の全ての組み合わせについて ViterbiSimu を回す。
+// 全ての組み合わせで同じ seed を使うので、復号器などの違いを同じ雑音で比べられる。
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::checkpoint;
+use crate::code::ConvolutionalCode;
+use crate::puncture::Puncturer;
+use crate::simu::{self, ViterbiSimu};
+use crate::snr::{Modulation, SnrUnit};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CodeConfig {
+    // ConvolutionalCode::parse の書き方
+    pub code: String,
+    // Puncturer::parse の書き方
+    #[serde(default)]
+    pub puncture: Option<String>,
+    pub decoders: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    #[serde(default = "default_channel")]
+    pub channel: String,
+    #[serde(default = "default_modulation")]
+    pub modulation: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnrConfig {
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    pub start: f64,
+    pub step: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StopConfig {
+    // 1つの SN で送る最大のフレーム数
+    pub frames: usize,
+    #[serde(default)]
+    pub max_errors: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Campaign {
+    pub seed: u64,
+    pub frame_len: usize,
+    #[serde(default = "default_confidence")]
+    pub confidence: f64,
+    #[serde(default)]
+    pub threads: Option<usize>,
+    #[serde(rename = "code")]
+    pub codes: Vec<CodeConfig>,
+    #[serde(rename = "channel", default = "default_channels")]
+    pub channels: Vec<ChannelConfig>,
+    pub snr: Vec<SnrConfig>,
+    pub stop: Vec<StopConfig>,
+}
+
+fn default_channel() -> String {
+    "awgn".to_string()
+}
+
+fn default_modulation() -> String {
+    "bpsk".to_string()
+}
+
+fn default_unit() -> String {
+    "ebn0".to_string()
+}
+
+fn default_confidence() -> f64 {
+    0.95
+}
+
+fn default_channels() -> Vec<ChannelConfig> {
+    vec![ChannelConfig { channel: default_channel(), modulation: default_modulation() }]
+}
+
+// 1つの組み合わせ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub code: CodeConfig,
+    pub decoder: String,
+    pub channel: ChannelConfig,
+    pub snr: SnrConfig,
+    pub stop: StopConfig,
+}
+
+impl Campaign {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    // 拡張子で TOML か JSON かを決める
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Campaign::from_toml(&text),
+            Some("json") => Campaign::from_json(&text),
+            _ => Err(format!("{}: config must be .toml or .json", path.display())),
+        }
+    }
+
+    // 全ての組み合わせ (符号, 復号器, 通信路, SN, 止める条件 の順に回す)
+    pub fn runs(&self) -> Vec<Run> {
+        let mut runs = Vec::new();
+        for code in &self.codes {
+            for decoder in &code.decoders {
+                for channel in &self.channels {
+                    for snr in &self.snr {
+                        for stop in &self.stop {
+                            runs.push(Run {
+                                code: code.clone(),
+                                decoder: decoder.clone(),
+                                channel: channel.clone(),
+                                snr: snr.clone(),
+                                stop: stop.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    // 組み合わせから ViterbiSimu を作る (まだ回さない)
+    pub fn simu(&self, run: &Run) -> Result<ViterbiSimu, String> {
+        let code = ConvolutionalCode::parse(&run.code.code)?;
+        let puncturer = run.code.puncture.as_deref().map(Puncturer::parse).transpose()?;
+        if run.channel.channel != "awgn" {
+            return Err(format!("unknown channel {}", run.channel.channel));
+        }
+        let modulation: Modulation = run.channel.modulation.parse()?;
+        let unit: SnrUnit = run.snr.unit.parse()?;
+        simu::check_settings(&code, &run.decoder, puncturer.as_ref(), (run.snr.start, run.snr.step, run.snr.end),
+                             self.frame_len, self.confidence)?;
+        let mut simu = ViterbiSimu::new(run.decoder.clone(), code, run.snr.start, run.snr.step, run.snr.end,
+                                        self.frame_len, run.stop.frames)
+            .with_unit(unit)
+            .with_modulation(modulation)
+            .with_confidence(self.confidence)
+            .with_seed(self.seed);
+        if let Some(puncturer) = puncturer {
+            simu = simu.with_puncturer(puncturer);
+        }
+        if let Some(max_errors) = run.stop.max_errors {
+            simu = simu.with_max_errors(max_errors);
+        }
+        if let Some(threads) = self.threads {
+            simu = simu.with_threads(threads);
+        }
+        Ok(simu)
+    }
+
+    // 全部の組み合わせを確かめてから順に回す
+    pub fn run(&self) -> Result<Vec<(Run, ViterbiSimu)>, String> {
+        let runs = self.runs();
+        let simus = runs.iter().map(|run| self.simu(run)).collect::<Result<Vec<_>, _>>()?;
+        Ok(runs.into_iter().zip(Campaign::run_all(simus)).collect())
+    }
+
+    // run と同じだが、dir に組み合わせごとの途中経過 (run-<番号>.json) を every ごとに残す
+    // 前に止まったところがあればそこから続ける
+    pub fn run_with_checkpoints(&self, dir: &Path, every: Duration) -> Result<Vec<(Run, ViterbiSimu)>, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let runs = self.runs();
+        let simus = runs
+            .iter()
+            .enumerate()
+            .map(|(i, run)| checkpoint::attach(self.simu(run)?, &dir.join(format!("run-{}.json", i)), every))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs.into_iter().zip(Campaign::run_all(simus)).collect())
+    }
+
+    fn run_all(mut simus: Vec<ViterbiSimu>) -> Vec<ViterbiSimu> {
+        for simu in &mut simus {
+            simu.simu();
+            simu.bit_per_error();
+        }
+        simus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+seed = 1
+frame_len = 64
+
+[[code]]
+code = "k3"
+decoders = ["hard", "soft"]
+
+[[snr]]
+start = 1.0
+step = 1.0
+end = 3.0
+
+[[stop]]
+frames = 100
+"#;
+
+    #[test]
+    fn runs_every_combination() {
+        let campaign = Campaign::from_toml(CONFIG).unwrap();
+        let runs = campaign.runs();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().all(|run| campaign.simu(run).is_ok()));
+    }
+
+    #[test]
+    fn example_campaign_is_valid() {
+        let campaign = Campaign::from_file("campaigns/soft_vs_hard.toml").unwrap();
+        assert!(campaign.runs().iter().all(|run| campaign.simu(run).is_ok()));
+    }
+
+    #[test]
+    fn frame_shorter_than_tail_is_an_error() {
+        let campaign = Campaign::from_toml(&CONFIG.replace("frame_len = 64", "frame_len = 2")).unwrap();
+        let run = &campaign.runs()[0];
+        assert!(campaign.simu(run).is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use crate::binary;
+use crate::box_muller;
+use crate::rng::SeededRng;
+
+// 符号化したビット列を送って、受信側で見える信号を返す
+pub trait Channel {
+    fn transmit(&mut self, bits: &[u8]) -> Vec<f64>;
+}
+
+// 0が-1にマッピングされて、ガウス通信路を通る
+#[derive(Debug, Clone)]
+pub struct Awgn {
+    pub sigma: f64,
+    pub rng: SeededRng,
+}
+
+impl Awgn {
+    pub fn new(sigma: f64, seed: u64) -> Self {
+        Awgn {
+            sigma,
+            rng: SeededRng::new(seed),
+        }
+    }
+}
+
+impl Channel for Awgn {
+    fn transmit(&mut self, bits: &[u8]) -> Vec<f64> {
+        bits.iter()
+            .map(|b| binary::bpsk(*b) + self.sigma * box_muller::box_muller(&mut self.rng))
+            .collect()
+    }
+}
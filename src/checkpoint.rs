@@ -0,0 +1,144 @@
+// 長いシミュレーションの途中経過をファイルに残して、止まっても続きから回せるようにする
+//
+// 乱数列は (seed, SN の番号, バッチの番号) から作るので、各 SN で次に使うバッチの番号が乱数の状態になる。
+// 途中経過は全ての SN のバッチが足し終わったところで取るので、続きから回しても一度に回したのと同じ結果になる。
+// 同じファイルから最大のフレーム数や誤り数を増やして回せば、前の結果に足していける。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error_stats::ErrorStats;
+use crate::simu::ViterbiSimu;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Checkpoint {
+    // ここから frame_len, seed までが一致しないと続きにならない
+    pub code: String,
+    pub puncture: Option<String>,
+    pub decoder: String,
+    pub modulation: String,
+    pub unit: String,
+    pub start: f64,
+    pub step: f64,
+    pub end: f64,
+    pub frame_len: usize,
+    pub seed: u64,
+    // 各 SN で次に使うバッチの番号
+    pub next_batch: Vec<usize>,
+    pub stats: Vec<ErrorStats>,
+    pub elapsed_secs: f64,
+}
+
+impl Checkpoint {
+    pub fn new(simu: &ViterbiSimu) -> Self {
+        Checkpoint {
+            code: simu.code.spec(),
+            puncture: simu.puncturer.as_ref().map(|p| p.spec()),
+            decoder: simu.way.clone(),
+            modulation: simu.modulation.to_string(),
+            unit: simu.unit.to_string(),
+            start: simu.start_db,
+            step: simu.tick_db,
+            end: simu.end_db,
+            frame_len: simu.bits_len,
+            seed: simu.seed,
+            next_batch: simu.next_batch.clone(),
+            stats: simu.stats.clone(),
+            elapsed_secs: simu.elapsed.as_secs_f64(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // 書いている途中で止まっても前のファイルが壊れないように、別のファイルに書いてから置き換える
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let text = serde_json::to_string(self).unwrap();
+        fs::write(&tmp, text)
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // simu と同じ設定で取ったものか確かめる (フレーム数・誤り数・スレッド数は変えてよい)
+    pub fn check(&self, simu: &ViterbiSimu) -> Result<(), String> {
+        let other = Checkpoint::new(simu);
+        let same = self.code == other.code
+            && self.puncture == other.puncture
+            && self.decoder == other.decoder
+            && self.modulation == other.modulation
+            && self.unit == other.unit
+            && self.start == other.start
+            && self.step == other.step
+            && self.end == other.end
+            && self.frame_len == other.frame_len
+            && self.seed == other.seed
+            && self.next_batch.len() == simu.len
+            && self.stats.len() == simu.len;
+        if !same {
+            return Err(format!("checkpoint of {} {} doesnt match this simulation", self.decoder, self.code));
+        }
+        Ok(())
+    }
+}
+
+// path に途中経過があればそこから続け、every ごとに path に途中経過を残すようにする
+pub fn attach(simu: ViterbiSimu, path: &Path, every: Duration) -> Result<ViterbiSimu, String> {
+    let mut simu = simu.with_checkpoint(path, every);
+    if path.exists() {
+        let checkpoint = Checkpoint::load(path)?;
+        checkpoint.check(&simu).map_err(|e| format!("{}: {}", path.display(), e))?;
+        simu.resume(&checkpoint);
+    }
+    Ok(simu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::ConvolutionalCode;
+    use crate::simu::test_simu;
+
+    fn simu(frames: usize) -> ViterbiSimu {
+        test_simu("soft", ConvolutionalCode::k3(), (2., 1., 4.), 64, frames)
+            .with_seed(3)
+            .with_max_errors(100)
+            .with_threads(3)
+    }
+
+    #[test]
+    fn extended_run_equals_single_run() {
+        let path = std::env::temp_dir().join(format!("rust_viterbi_checkpoint_{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut first = attach(simu(500), &path, Duration::from_secs(60)).unwrap();
+        first.simu();
+        let mut extended = attach(simu(1000), &path, Duration::from_secs(60)).unwrap();
+        assert_eq!(extended.stats, first.stats);
+        extended.simu();
+        fs::remove_file(&path).unwrap();
+
+        let mut single = simu(1000);
+        single.simu();
+        assert_eq!(extended.stats, single.stats);
+        assert_eq!(extended.next_batch, single.next_batch);
+        assert_eq!(single.stats[2].frames, 1000);
+    }
+
+    #[test]
+    fn different_settings_are_rejected() {
+        let checkpoint = Checkpoint::new(&simu(500));
+        assert!(checkpoint.check(&simu(1000)).is_ok());
+        assert!(checkpoint.check(&simu(500).with_seed(4)).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+use crate::binary;
+use crate::spectrum::{self, DistanceSpectrum};
+use crate::trellis::Trellis;
+
+// 生成多項式から作る畳み込み符号 (rate k/n)
+//
+// 入力 i ごとにシフトレジスタを持ち、generators[i][j] は入力 i のレジスタから出力 j への生成多項式。
+// 生成多項式は8進数で書き、最上位ビットが今入力したビット、
+// 最下位ビットが一番古いビットにかかる。
+// 状態は各入力のレジスタ (constraint_lengths[i] - 1 ビット) を入力0から順に並べたもので、
+// それぞれのレジスタは最上位ビットが一番新しいビット。
+// 入力シンボル・出力シンボルは入力0・出力0が最上位ビット。
+#[derive(Debug, Clone)]
+pub struct ConvolutionalCode {
+    pub constraint_lengths: Vec<usize>,
+    pub generators: Vec<Vec<usize>>,
+    pub k: usize,
+    pub n: usize,
+    pub num_states: usize,
+    // [state][input symbol] -> next state
+    pub next_state: Vec<Vec<usize>>,
+    // [state][input symbol] -> output symbol
+    pub output: Vec<Vec<usize>>,
+}
+
+// parse で受け付けるトレリスの大きさ (状態数 × 入力シンボル数 = 2^(memory + k)) の上限
+pub const MAX_TRELLIS_BITS: usize = 24;
+// 出力シンボルは usize に詰めるので、出力数の上限
+pub const MAX_OUTPUTS: usize = 32;
+
+impl ConvolutionalCode {
+    // rate 1/n
+    pub fn new(constraint_length: usize, generators: &[usize]) -> Self {
+        ConvolutionalCode::with_inputs(&[constraint_length], &[generators.to_vec()])
+    }
+
+    // rate k/n
+    pub fn with_inputs(constraint_lengths: &[usize], generators: &[Vec<usize>]) -> Self {
+        let k = constraint_lengths.len();
+        if k == 0 || generators.len() != k {
+            panic!("cant make code with {} constraint lengths and {} generator rows",
+                   k, generators.len());
+        }
+        let n = generators[0].len();
+        if n == 0 {
+            panic!("cant make code without generator polynomials");
+        }
+        for (constraint_length, row) in constraint_lengths.iter().zip(generators) {
+            if *constraint_length < 1 {
+                panic!("cant make code with constraint length {}", constraint_length);
+            }
+            if row.len() != n {
+                panic!("every generator row must have {} polynomials: {:?}", n, generators);
+            }
+            for g in row {
+                if g.checked_shr(*constraint_length as u32).is_some_and(|rest| rest != 0) {
+                    panic!("generator {:o} is too long for constraint length {}", g, constraint_length);
+                }
+            }
+        }
+        let memories: Vec<usize> = constraint_lengths.iter().map(|c| c - 1).collect();
+        let memory: usize = memories.iter().sum();
+        if memory == 0 {
+            panic!("cant make code without memory");
+        }
+        let num_states = 1 << memory;
+        let num_inputs = 1 << k;
+        let mut next_state = vec![vec![0; num_inputs]; num_states];
+        let mut output = vec![vec![0; num_inputs]; num_states];
+        for state in 0..num_states {
+            for input in 0..num_inputs {
+                let mut next = 0;
+                let mut out = 0;
+                let mut rest = memory;
+                for (i, m) in memories.iter().enumerate() {
+                    rest -= m;
+                    let bit = (input >> (k - 1 - i)) & 1;
+                    let register = (bit << m) | ((state >> rest) & ((1 << m) - 1));
+                    next = (next << m) | (register >> 1);
+                    for (j, g) in generators[i].iter().enumerate() {
+                        out ^= ((g & register).count_ones() as usize & 1) << (n - 1 - j);
+                    }
+                }
+                next_state[state][input] = next;
+                output[state][input] = out;
+            }
+        }
+        ConvolutionalCode {
+            constraint_lengths: constraint_lengths.to_vec(),
+            generators: generators.to_vec(),
+            k,
+            n,
+            num_states,
+            next_state,
+            output,
+        }
+    }
+
+    // K=3 (7, 5)
+    pub fn k3() -> Self {
+        ConvolutionalCode::new(3, &[0o7, 0o5])
+    }
+
+    // K=7 (171, 133) NASA standard code
+    pub fn nasa() -> Self {
+        ConvolutionalCode::new(7, &[0o171, 0o133])
+    }
+
+    // K=7 (133, 171) IEEE 802.11a/g/n (nasa と出力の順番が逆で、Puncturer::ieee80211 はこの順番)
+    pub fn ieee80211() -> Self {
+        ConvolutionalCode::new(7, &[0o133, 0o171])
+    }
+
+    // K=7 (133, 171, 165) rate 1/3 (LTE)
+    pub fn lte() -> Self {
+        ConvolutionalCode::new(7, &[0o133, 0o171, 0o165])
+    }
+
+    // rate 2/3, G(D) = [[1+D, D, 1+D], [D, 1, 1]]
+    pub fn rate_2_3() -> Self {
+        ConvolutionalCode::with_inputs(&[2, 2], &[vec![0o3, 0o1, 0o3], vec![0o1, 0o2, 0o2]])
+    }
+
+    // "k3" などのプリセット名か、"拘束長:生成多項式,..." (8進数) で書いた符号
+    // rate k/n は入力ごとに ";" で区切る (例 "2,2:3,1,3;1,2,2")
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "k3" => return Ok(ConvolutionalCode::k3()),
+            "nasa" => return Ok(ConvolutionalCode::nasa()),
+            "ieee80211" => return Ok(ConvolutionalCode::ieee80211()),
+            "lte" => return Ok(ConvolutionalCode::lte()),
+            "rate-2-3" => return Ok(ConvolutionalCode::rate_2_3()),
+            _ => {}
+        }
+        let (constraint_lengths, generators) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("unknown code {} (expected a preset or K:g,g,...)", spec))?;
+        let constraint_lengths = constraint_lengths
+            .split(',')
+            .map(|c| c.trim().parse::<usize>().map_err(|e| format!("bad constraint length {}: {}", c, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let generators = generators
+            .split(';')
+            .map(|row| {
+                row.split(',')
+                   .map(|g| usize::from_str_radix(g.trim(), 8).map_err(|e| format!("bad generator {}: {}", g, e)))
+                   .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if constraint_lengths.len() != generators.len() {
+            return Err(format!("{} constraint lengths for {} generator rows", constraint_lengths.len(), generators.len()));
+        }
+        if generators.iter().any(|row| row.len() != generators[0].len()) {
+            return Err(format!("every generator row of {} must have the same number of polynomials", spec));
+        }
+        for (c, row) in constraint_lengths.iter().zip(&generators) {
+            if *c < 1 || row.iter().any(|g| g.checked_shr(*c as u32).is_some_and(|rest| rest != 0)) {
+                return Err(format!("generators {:?} dont fit constraint length {}", row, c));
+            }
+        }
+        if constraint_lengths.iter().all(|c| *c == 1) {
+            return Err(format!("code {} has no memory", spec));
+        }
+        let memory: usize = constraint_lengths.iter().map(|c| c - 1).sum();
+        if memory + constraint_lengths.len() > MAX_TRELLIS_BITS {
+            return Err(format!("code {} is too large (memory + inputs must be at most {})", spec, MAX_TRELLIS_BITS));
+        }
+        if generators[0].len() > MAX_OUTPUTS {
+            return Err(format!("code {} has more than {} outputs", spec, MAX_OUTPUTS));
+        }
+        Ok(ConvolutionalCode::with_inputs(&constraint_lengths, &generators))
+    }
+
+    // parse で読める書き方 (プリセットも "拘束長:生成多項式" で書く)
+    pub fn spec(&self) -> String {
+        let constraint_lengths: Vec<String> = self.constraint_lengths.iter().map(|c| c.to_string()).collect();
+        let generators: Vec<String> = self.generators
+            .iter()
+            .map(|row| row.iter().map(|g| format!("{:o}", g)).collect::<Vec<_>>().join(","))
+            .collect();
+        format!("{}:{}", constraint_lengths.join(","), generators.join(";"))
+    }
+
+    // 状態のビット数
+    pub fn memory(&self) -> usize {
+        self.constraint_lengths.iter().map(|c| c - 1).sum()
+    }
+
+    // 一番長いレジスタの拘束長
+    pub fn constraint_length(&self) -> usize {
+        *self.constraint_lengths.iter().max().unwrap()
+    }
+
+    // 状態0に戻すのに必要な0の入力シンボル数
+    pub fn termination_len(&self) -> usize {
+        self.constraint_length() - 1
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.k as f64 / self.n as f64
+    }
+
+    // 状態0から符号化する
+    // bits は k ビットずつ、返り値は n ビットずつのシンボルに区切られる
+    pub fn encode(&self, bits: &[u8]) -> Vec<u8> {
+        self.encode_from(0, bits).0
+    }
+
+    // state から符号化して、符号と最後の状態を返す
+    pub fn encode_from(&self, state: usize, bits: &[u8]) -> (Vec<u8>, usize) {
+        if !bits.len().is_multiple_of(self.k) {
+            panic!("cant encode {} bits with {} inputs", bits.len(), self.k);
+        }
+        let mut state = state;
+        let mut coded = Vec::with_capacity(bits.len() / self.k * self.n);
+        for symbol in bits.chunks(self.k) {
+            let input = binary::pack(symbol);
+            coded.extend(binary::unpack(self.output[state][input], self.n));
+            state = self.next_state[state][input];
+        }
+        (coded, state)
+    }
+
+    // テイルバイティング符号化 (最初の状態 = 最後の状態)
+    // フィードフォワード符号なので最後の状態は最後の入力だけで決まり、
+    // 一度状態0から符号化したときの最後の状態から始めればいい
+    pub fn encode_tail_biting(&self, bits: &[u8]) -> Vec<u8> {
+        if bits.len() / self.k < self.termination_len() {
+            panic!("cant tail-bite {} bits with memory {}", bits.len(), self.memory());
+        }
+        let (_, last) = self.encode_from(0, bits);
+        let (coded, end) = self.encode_from(last, bits);
+        assert_eq!(last, end);
+        coded
+    }
+
+    // rate 1/n の生成多項式の GF(2) 上の最大公約多項式
+    pub fn generator_gcd(&self) -> Option<usize> {
+        if self.k != 1 {
+            return None;
+        }
+        Some(self.generators[0].iter().fold(0, |a, b| gcd_gf2(a, *b)))
+    }
+
+    // 破滅的符号か
+    // rate 1/n なら生成多項式の最大公約多項式が D^l でないとき (Massey-Sain)、
+    // rate k/n なら状態図に出力0のループがあるとき
+    pub fn is_catastrophic(&self) -> bool {
+        match self.generator_gcd() {
+            Some(gcd) => !gcd.is_power_of_two(),
+            None => Trellis::new(self).zero_weight_loop().is_some(),
+        }
+    }
+
+    pub fn free_distance(&self) -> usize {
+        spectrum::free_distance(&Trellis::new(self))
+    }
+
+    // 自由距離から terms 個の距離の A_d と B_d
+    pub fn distance_spectrum(&self, terms: usize) -> DistanceSpectrum {
+        DistanceSpectrum::new(&Trellis::new(self), terms)
+    }
+}
+
+// GF(2) 上の多項式 (ビット列) の最大公約多項式
+fn gcd_gf2(a: usize, b: usize) -> usize {
+    let (mut a, mut b) = (a, b);
+    while b != 0 {
+        // a mod b
+        while a != 0 && a.leading_zeros() <= b.leading_zeros() {
+            a ^= b << (b.leading_zeros() - a.leading_zeros());
+        }
+        std::mem::swap(&mut a, &mut b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 教科書の例: 1011 に終端の 00 をつけて符号化する
+    #[test]
+    fn encodes_k3_known_answer() {
+        let k3 = ConvolutionalCode::k3();
+        assert_eq!(k3.encode(&[1, 0, 1, 1, 0, 0]), vec![1, 1, 1, 0, 0, 0, 0, 1, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn parse_round_trips_spec() {
+        for spec in ["k3", "nasa", "ieee80211", "lte", "rate-2-3", "5:23,35"] {
+            let code = ConvolutionalCode::parse(spec).unwrap();
+            let again = ConvolutionalCode::parse(&code.spec()).unwrap();
+            assert_eq!(again.generators, code.generators);
+            assert_eq!(again.constraint_lengths, code.constraint_lengths);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_codes() {
+        for spec in ["1:1,1", "2,2:3,1,3;1,2", "3:17,5", "3:7,5;7,5", "3:7,9", "k9", "64:7,5", "50:7,5", "25:7,5", "99999999999999999999:7,5"] {
+            assert!(ConvolutionalCode::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn detects_catastrophic_rate_1_n_codes() {
+        // 最上位ビットが今の入力 (D^0) なので 6 = 1 + D, 5 = 1 + D^2 = (1 + D)^2 で、最大公約多項式は 1 + D
+        let code = ConvolutionalCode::parse("3:6,5").unwrap();
+        assert_eq!(code.generator_gcd(), Some(0o3));
+        assert!(code.is_catastrophic());
+        assert!(Trellis::new(&code).zero_weight_loop().is_some());
+
+        let code = ConvolutionalCode::k3();
+        assert_eq!(code.generator_gcd(), Some(1));
+        assert!(!code.is_catastrophic());
+        assert!(Trellis::new(&code).zero_weight_loop().is_none());
+    }
+
+    #[test]
+    fn detects_catastrophic_rate_k_n_codes() {
+        // 入力0の生成多項式が全部 1 + D なので、入力0に1を入れ続けると出力が0のまま状態1に留まる
+        let code = ConvolutionalCode::parse("2,2:3,3,3;1,2,2").unwrap();
+        assert_eq!(code.generator_gcd(), None);
+        assert!(Trellis::new(&code).zero_weight_loop().is_some());
+        assert!(code.is_catastrophic());
+
+        assert!(Trellis::new(&ConvolutionalCode::rate_2_3()).zero_weight_loop().is_none());
+        assert!(!ConvolutionalCode::rate_2_3().is_catastrophic());
+    }
+    #[test]
+    fn tail_biting_starts_and_ends_in_same_state() {
+        for code in [ConvolutionalCode::k3(), ConvolutionalCode::nasa(), ConvolutionalCode::rate_2_3()] {
+            let bits: Vec<u8> = (0..40 * code.k).map(|i| ((i * 7 + 3) % 5 < 2) as u8).collect();
+            let (_, start) = code.encode_from(0, &bits);
+            assert_ne!(start, 0);
+            let (coded, end) = code.encode_from(start, &bits);
+            assert_eq!(end, start);
+            assert_eq!(code.encode_tail_biting(&bits), coded);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use crate::code::ConvolutionalCode;
+use crate::puncture::Puncturer;
+
+// フレームの終わらせ方
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Termination {
+    // 0を入れて状態0に戻す
+    Zero,
+    // 何もしない (ストリーム)
+    Truncated,
+    // 最初の状態 = 最後の状態
+    TailBiting,
+}
+
+impl FromStr for Termination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(Termination::Zero),
+            "truncated" => Ok(Termination::Truncated),
+            "tail-biting" => Ok(Termination::TailBiting),
+            _ => Err(format!("unknown termination {} (zero, truncated, tail-biting)", s)),
+        }
+    }
+}
+
+// 情報ビットを符号化して、パンクチャして送るビット列にする
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    pub code: ConvolutionalCode,
+    pub termination: Termination,
+    pub puncturer: Option<Puncturer>,
+}
+
+impl Encoder {
+    pub fn new(code: ConvolutionalCode, termination: Termination) -> Self {
+        Encoder {
+            code,
+            termination,
+            puncturer: None,
+        }
+    }
+
+    pub fn with_puncturer(mut self, puncturer: Puncturer) -> Self {
+        // 符号の出力数とパターンの行数が合っているか確かめる
+        puncturer.rate(&self.code);
+        self.puncturer = Some(puncturer);
+        self
+    }
+
+    // パンクチャ後の符号化率 (終端のビットは数えない)
+    pub fn rate(&self) -> f64 {
+        self.puncturer.as_ref().map_or(self.code.rate(), |p| p.rate(&self.code))
+    }
+
+    // len シンボルのフレームで送る1ビットあたりの情報ビット数 (0で終端するなら終端のビットも数える)
+    pub fn frame_rate(&self, len: usize) -> f64 {
+        self.info_len(len) as f64 / self.sent_len(len) as f64
+    }
+
+    // len シンボルのフレームに入る情報ビット数
+    pub fn info_len(&self, len: usize) -> usize {
+        match self.termination {
+            Termination::Zero => (len - self.code.termination_len()) * self.code.k,
+            Termination::Truncated | Termination::TailBiting => len * self.code.k,
+        }
+    }
+
+    // info_len ビットの情報を符号化したフレームのシンボル数 (info_len の逆)
+    pub fn symbol_len(&self, info_len: usize) -> usize {
+        match self.termination {
+            Termination::Zero => info_len / self.code.k + self.code.termination_len(),
+            Termination::Truncated | Termination::TailBiting => info_len / self.code.k,
+        }
+    }
+
+    // 1フレームに要る最小のシンボル数
+    // 0で終端するなら終端の分のほかに1シンボル、テイルバイティングなら最初の状態を決められるだけ要る
+    pub fn min_len(&self) -> usize {
+        match self.termination {
+            Termination::Zero => self.code.termination_len() + 1,
+            Termination::TailBiting => self.code.termination_len().max(1),
+            Termination::Truncated => 1,
+        }
+    }
+
+    // len シンボルのフレームで送るビット数
+    pub fn sent_len(&self, len: usize) -> usize {
+        match &self.puncturer {
+            Some(p) => p.punctured_len(len * self.code.n),
+            None => len * self.code.n,
+        }
+    }
+
+    // received ビット受信したフレームのシンボル数
+    // sent_len(len) >= received になる最小の len
+    pub fn frame_len(&self, received: usize) -> usize {
+        let n = self.code.n;
+        match &self.puncturer {
+            Some(p) => {
+                // 1周期で kept() ビット送るので、残りを送るのに要るシンボル数を最後の周期の中で探す
+                let rest = received % p.kept();
+                let partial = (0..=p.period()).find(|len| p.punctured_len(len * n) >= rest).unwrap();
+                received / p.kept() * p.period() + partial
+            }
+            None => received.div_ceil(n),
+        }
+    }
+
+    // 情報ビットを符号化して、送るビット列を返す
+    pub fn encode(&self, info: &[u8]) -> Vec<u8> {
+        let coded = match self.termination {
+            Termination::Zero => {
+                let mut bits = info.to_vec();
+                bits.resize(info.len() + self.code.termination_len() * self.code.k, 0);
+                self.code.encode(&bits)
+            }
+            Termination::Truncated => self.code.encode(info),
+            Termination::TailBiting => self.code.encode_tail_biting(info),
+        };
+        match &self.puncturer {
+            Some(p) => p.puncture(&coded),
+            None => coded,
+        }
+    }
+
+    // 受信した信号を len シンボル分の復号器の入力に戻す
+    pub fn depuncture(&self, received: &[f64], len: usize) -> Vec<f64> {
+        match &self.puncturer {
+            Some(p) => p.depuncture(received, len * self.code.n),
+            None => received.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_len_is_shortest_frame_sending_received_bits() {
+        let punctured = Encoder::new(ConvolutionalCode::nasa(), Termination::Zero).with_puncturer(Puncturer::dvb("7/8"));
+        let rate_2_3 = Encoder::new(ConvolutionalCode::rate_2_3(), Termination::Zero);
+        for encoder in [punctured, rate_2_3] {
+            for received in 0..100 {
+                let expected = (0..).find(|len| encoder.sent_len(*len) >= received).unwrap();
+                assert_eq!(encoder.frame_len(received), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+// フレーム誤りと誤りイベントの集計
+// 誤りイベント: gap ビット未満の正しいビットをはさんで続く誤りのまとまり
+// (ビタビ復号の誤りは正しい経路から外れて戻るまでの間にまとまって出る)
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ErrorStats {
+    pub bits: usize,
+    pub errors: usize,
+    pub frames: usize,
+    pub frame_errors: usize,
+    pub events: usize,
+    // bursts[l] は最初の誤りから最後の誤りまでの長さが l ビットの誤りイベントの数
+    pub bursts: Vec<usize>,
+    // positions[i] はフレームの i ビット目が誤った回数
+    pub positions: Vec<usize>,
+}
+
+impl ErrorStats {
+    pub fn new() -> Self {
+        ErrorStats::default()
+    }
+
+    pub fn add_frame(&mut self, sent: &[u8], decoded: &[u8], gap: usize) {
+        if self.positions.len() < sent.len() {
+            self.positions.resize(sent.len(), 0);
+        }
+        self.frames += 1;
+        self.bits += sent.len();
+        // 今のイベントの (最初の誤り, 最後の誤り)
+        let mut event: Option<(usize, usize)> = None;
+        for (i, (s, d)) in sent.iter().zip(decoded).enumerate() {
+            if s == d {
+                continue;
+            }
+            self.errors += 1;
+            self.positions[i] += 1;
+            event = match event {
+                Some((first, last)) if i - last <= gap => Some((first, i)),
+                Some((first, last)) => {
+                    self.add_burst(last - first + 1);
+                    Some((i, i))
+                }
+                None => Some((i, i)),
+            };
+        }
+        if let Some((first, last)) = event {
+            self.add_burst(last - first + 1);
+            self.frame_errors += 1;
+        }
+    }
+
+    fn add_burst(&mut self, len: usize) {
+        if self.bursts.len() <= len {
+            self.bursts.resize(len + 1, 0);
+        }
+        self.bursts[len] += 1;
+        self.events += 1;
+    }
+
+    pub fn merge(&mut self, other: &ErrorStats) {
+        self.bits += other.bits;
+        self.errors += other.errors;
+        self.frames += other.frames;
+        self.frame_errors += other.frame_errors;
+        self.events += other.events;
+        for (mine, theirs) in [(&mut self.bursts, &other.bursts), (&mut self.positions, &other.positions)] {
+            if mine.len() < theirs.len() {
+                mine.resize(theirs.len(), 0);
+            }
+            for (m, t) in mine.iter_mut().zip(theirs) {
+                *m += t;
+            }
+        }
+    }
+
+    pub fn ber(&self) -> f64 {
+        if self.bits == 0 { 0. } else { self.errors as f64 / self.bits as f64 }
+    }
+
+    pub fn fer(&self) -> f64 {
+        if self.frames == 0 { 0. } else { self.frame_errors as f64 / self.frames as f64 }
+    }
+
+    // 誤りイベントの平均の長さ
+    pub fn mean_burst(&self) -> f64 {
+        if self.events == 0 {
+            return 0.;
+        }
+        let total: usize = self.bursts.iter().enumerate().map(|(len, count)| len * count).sum();
+        total as f64 / self.events as f64
+    }
+}
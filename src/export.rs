@@ -0,0 +1,143 @@
+// シミュレーション結果を CSV / JSON で書き出す
+
+use serde::Serialize;
+
+use crate::simu::{BerPoint, ViterbiSimu};
+
+// 1回の ViterbiSimu の設定と結果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    // ConvolutionalCode::parse で読める書き方
+    pub code: String,
+    pub puncture: Option<String>,
+    pub rate: f64,
+    pub decoder: String,
+    pub channel: String,
+    pub modulation: String,
+    pub unit: String,
+    pub seed: u64,
+    pub frame_len: usize,
+    pub max_frames: usize,
+    pub max_errors: Option<usize>,
+    pub confidence: f64,
+    pub runtime_secs: f64,
+    pub points: Vec<BerPoint>,
+}
+
+impl Report {
+    // simu と bit_per_error の後に呼ぶ
+    pub fn new(simu: &ViterbiSimu) -> Self {
+        Report {
+            code: simu.code.spec(),
+            puncture: simu.puncturer.as_ref().map(|p| p.spec()),
+            rate: simu.rate,
+            decoder: simu.way.clone(),
+            channel: "awgn".to_string(),
+            modulation: simu.modulation.to_string(),
+            unit: simu.unit.to_string(),
+            seed: simu.seed,
+            frame_len: simu.bits_len,
+            max_frames: simu.iteration,
+            max_errors: simu.max_errors,
+            confidence: simu.confidence,
+            runtime_secs: simu.elapsed.as_secs_f64(),
+            points: simu.points.clone(),
+        }
+    }
+}
+
+pub fn to_json(reports: &[Report]) -> String {
+    serde_json::to_string_pretty(reports).unwrap()
+}
+
+const CSV_HEADER: &[&str] = &[
+    "code", "puncture", "rate", "decoder", "channel", "modulation", "unit", "seed", "frame_len", "max_frames",
+    "max_errors", "confidence", "runtime_secs", "snr", "ebn0", "esn0", "bits", "errors", "frames", "frame_errors",
+    "events", "mean_burst", "ber", "fer", "wilson_low", "wilson_high", "clopper_pearson_low", "clopper_pearson_high",
+];
+
+// "," や '"' を含む値は '"' で囲む
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// 1行に1つの SN 点 (設定は毎行くり返す)
+pub fn to_csv(reports: &[Report]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\n";
+    for r in reports {
+        for p in &r.points {
+            let row = [
+                r.code.clone(),
+                r.puncture.clone().unwrap_or_default(),
+                r.rate.to_string(),
+                r.decoder.clone(),
+                r.channel.clone(),
+                r.modulation.clone(),
+                r.unit.clone(),
+                r.seed.to_string(),
+                r.frame_len.to_string(),
+                r.max_frames.to_string(),
+                r.max_errors.map_or(String::new(), |m| m.to_string()),
+                r.confidence.to_string(),
+                r.runtime_secs.to_string(),
+                p.snr.to_string(),
+                p.ebn0.to_string(),
+                p.esn0.to_string(),
+                p.bits.to_string(),
+                p.errors.to_string(),
+                p.frames.to_string(),
+                p.frame_errors.to_string(),
+                p.events.to_string(),
+                p.mean_burst.to_string(),
+                p.ber.to_string(),
+                p.fer.to_string(),
+                p.wilson.0.to_string(),
+                p.wilson.1.to_string(),
+                p.clopper_pearson.0.to_string(),
+                p.clopper_pearson.1.to_string(),
+            ];
+            csv += &row.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+            csv += "\n";
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::ConvolutionalCode;
+    use crate::simu::test_simu;
+
+    fn report() -> Report {
+        let mut vs = test_simu("soft", ConvolutionalCode::k3(), (1., 1., 2.), 32, 100);
+        vs.simu();
+        vs.bit_per_error();
+        Report::new(&vs)
+    }
+
+    #[test]
+    fn csv_rows_match_header() {
+        let csv = to_csv(&[report()]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // 符号の書き方は "," を含むので '"' で囲まれる
+        assert!(lines[1].starts_with("\"3:7,5\","));
+        assert_eq!(lines[1].replace("\"3:7,5\"", "code").split(',').count(), CSV_HEADER.len());
+    }
+
+    #[test]
+    fn json_has_error_events() {
+        let report = report();
+        let json: serde_json::Value = serde_json::from_str(&to_json(std::slice::from_ref(&report))).unwrap();
+        let point = &json[0]["points"][0];
+        let bursts: Vec<usize> = serde_json::from_value(point["bursts"].clone()).unwrap();
+        let positions: Vec<usize> = serde_json::from_value(point["positions"].clone()).unwrap();
+        assert_eq!(bursts.iter().sum::<usize>(), report.points[0].events);
+        assert_eq!(positions.iter().sum::<usize>(), report.points[0].errors);
+    }
+}
@@ -0,0 +1,36 @@
+pub mod binary;
+mod box_muller;
+pub mod campaign;
+pub mod checkpoint;
+pub mod channel;
+pub mod code;
+pub mod encoder;
+pub mod error_stats;
+pub mod export;
+pub mod metric;
+pub mod plot;
+pub mod puncture;
+pub mod rng;
+pub mod simu;
+pub mod snr;
+pub mod source;
+pub mod spectrum;
+pub mod stats;
+pub mod theory;
+pub mod trellis;
+pub mod viterbi;
+
+pub use campaign::Campaign;
+pub use checkpoint::Checkpoint;
+pub use channel::{Awgn, Channel};
+pub use code::ConvolutionalCode;
+pub use encoder::{Encoder, Termination};
+pub use error_stats::ErrorStats;
+pub use export::Report;
+pub use puncture::Puncturer;
+pub use simu::{BerPoint, ViterbiSimu};
+pub use snr::{Modulation, SnrUnit};
+pub use source::Source;
+pub use spectrum::DistanceSpectrum;
+pub use trellis::Trellis;
+pub use viterbi::{ChannelDecoder, Decoder};
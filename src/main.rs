@@ -1,41 +1,442 @@
-#![allow(non_snake_case)]
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::Duration;
 
-use gnuplot::{Figure, Caption, Color, Graph, AxesCommon};
+use clap::{Parser, Subcommand, ValueEnum};
 
-mod trellis;
-mod viterbi;
-mod box_muller;
+use rust_viterbi::{plot, Awgn, Campaign, Channel, ConvolutionalCode, Encoder, Modulation, Puncturer, SnrUnit, Termination,
+                   Trellis, ViterbiSimu};
+use rust_viterbi::checkpoint::{self, Checkpoint};
+use rust_viterbi::export::{self, Report};
+use rust_viterbi::plot::ErrorBars;
+use rust_viterbi::simu;
+use rust_viterbi::viterbi::find_decoder;
 
-use viterbi::Viterbi;
-use crate::viterbi::ViterbiSimu;
+#[derive(Parser)]
+#[command(about = "Convolutional code simulator")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Measure BER over an SNR sweep
+    Simulate(SimulateArgs),
+    /// Encode 0/1 information bits
+    Encode(EncodeArgs),
+    /// Decode received BPSK samples (0 is sent as -1, 1 as +1)
+    Decode(DecodeArgs),
+    /// Print rate, free distance, distance spectrum and catastrophicity of a code
+    Analyze(AnalyzeArgs),
+    /// Run every combination described in a TOML or JSON config file
+    Campaign(CampaignArgs),
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum ChannelKind {
+    Awgn,
+}
+
+#[derive(clap::Args)]
+struct CodeArgs {
+    /// Preset (k3, nasa, ieee80211, lte, rate-2-3) or K:g,g,... in octal, rows separated by ';'
+    #[arg(long, default_value = "k3")]
+    code: String,
+    /// Puncturing: ieee80211:3/4, dvb:7/8 or rows of 0/1 separated by ';'
+    #[arg(long)]
+    puncture: Option<String>,
+}
+
+impl CodeArgs {
+    fn code(&self) -> ConvolutionalCode {
+        ConvolutionalCode::parse(&self.code).unwrap_or_else(|e| fail(&e))
+    }
+
+    fn puncturer(&self) -> Option<Puncturer> {
+        let puncturer = self.puncture.as_ref().map(|p| Puncturer::parse(p).unwrap_or_else(|e| fail(&e)))?;
+        if puncturer.pattern.len() != self.code().n {
+            fail(&format!("puncturing pattern {} doesnt fit code {}", puncturer.spec(), self.code));
+        }
+        Some(puncturer)
+    }
+}
+
+#[derive(clap::Args)]
+struct PlotArgs {
+    /// Show the BER curves in a gnuplot window
+    #[arg(long)]
+    plot: bool,
+    /// Save the BER curves to a .png, .svg or .pdf file (no terminal needed)
+    #[arg(long)]
+    plot_file: Option<PathBuf>,
+    /// Confidence interval drawn as error bars: off, wilson or clopper-pearson
+    #[arg(long, default_value = "clopper-pearson")]
+    error_bars: ErrorBars,
+}
+
+#[derive(clap::Args)]
+struct CheckpointArgs {
+    /// Keep partial results in this directory and resume from it when run again
+    #[arg(long)]
+    checkpoint_dir: Option<PathBuf>,
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60)]
+    checkpoint_every: u64,
+}
+
+impl CheckpointArgs {
+    fn every(&self) -> Duration {
+        Duration::from_secs(self.checkpoint_every)
+    }
+}
+
+#[derive(clap::Args)]
+struct SimulateArgs {
+    #[command(flatten)]
+    code: CodeArgs,
+    /// Repeat to compare several decoders on the same noise
+    #[arg(long = "decoder", default_value = "soft")]
+    decoders: Vec<String>,
+    #[arg(long, value_enum, default_value = "awgn")]
+    channel: ChannelKind,
+    /// bpsk or qpsk
+    #[arg(long, default_value = "bpsk")]
+    modulation: Modulation,
+    /// Unit of the SNR range: ebn0, esn0 or snr
+    #[arg(long, default_value = "ebn0")]
+    unit: SnrUnit,
+    #[arg(long, default_value_t = 1.0)]
+    start: f64,
+    #[arg(long, default_value_t = 0.5)]
+    step: f64,
+    #[arg(long, default_value_t = 5.0)]
+    end: f64,
+    /// Frame length in trellis steps (including the tail)
+    #[arg(long, default_value_t = 1024)]
+    frame_len: usize,
+    /// Maximum number of frames per SNR point
+    #[arg(long, default_value_t = 10000)]
+    frames: usize,
+    /// Stop an SNR point once it has this many bit errors. Checked only between 100-frame batches,
+    /// so at low SNR the count can overshoot the limit by up to a batch's worth of errors
+    #[arg(long)]
+    max_errors: Option<usize>,
+    #[arg(long, default_value_t = 0.95)]
+    confidence: f64,
+    /// Master seed (random if omitted; the seed used is printed)
+    #[arg(long)]
+    seed: Option<u64>,
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Write the results to this file instead of stdout (.csv and .json are machine-readable)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    plot: PlotArgs,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+}
+
+#[derive(clap::Args)]
+struct EncodeArgs {
+    #[command(flatten)]
+    code: CodeArgs,
+    /// zero, truncated or tail-biting
+    #[arg(long, default_value = "zero")]
+    termination: Termination,
+    /// Send the coded bits through an AWGN channel with this sigma and print the samples
+    #[arg(long)]
+    sigma: Option<f64>,
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Read from this file instead of stdin
+    #[arg(long, short)]
+    input: Option<PathBuf>,
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct DecodeArgs {
+    #[command(flatten)]
+    code: CodeArgs,
+    #[arg(long, default_value = "soft")]
+    decoder: String,
+    /// Noise standard deviation (used by SOVA and BCJR)
+    #[arg(long, default_value_t = 1.0)]
+    sigma: f64,
+    /// Seed for random tie-breaking
+    #[arg(long)]
+    seed: Option<u64>,
+    #[arg(long, short)]
+    input: Option<PathBuf>,
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct AnalyzeArgs {
+    #[command(flatten)]
+    code: CodeArgs,
+    /// Number of distance spectrum terms
+    #[arg(long, default_value_t = 10)]
+    terms: usize,
+}
+
+#[derive(clap::Args)]
+struct CampaignArgs {
+    /// Config file (.toml or .json)
+    config: PathBuf,
+    /// Write the results to this file instead of stdout (.csv and .json are machine-readable)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    plot: PlotArgs,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+}
 
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1)
+}
+
+fn read_input(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e))),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).unwrap_or_else(|e| fail(&e.to_string()));
+            input
+        }
+    }
+}
+
+fn write_output(path: &Option<PathBuf>, text: &str) {
+    match path {
+        Some(path) => fs::write(path, text).unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e))),
+        None => print!("{}", text),
+    }
+}
+
+// 破滅的符号は符号化・復号の前に警告する (シミュレーションでは ViterbiSimu が止める)
+fn warn_catastrophic(code: &ConvolutionalCode, spec: &str) {
+    if code.is_catastrophic() {
+        eprintln!("warning: code {} is catastrophic", spec);
+    }
+}
+
+fn simulate(args: SimulateArgs) {
+    let ChannelKind::Awgn = args.channel;
+    let code = args.code.code();
+    let puncturer = args.code.puncturer();
+    for decoder in &args.decoders {
+        simu::check_settings(&code, decoder, puncturer.as_ref(), (args.start, args.step, args.end), args.frame_len,
+                             args.confidence)
+            .unwrap_or_else(|e| fail(&e));
+    }
+    // 全ての復号器で同じ雑音を使う
+    // 途中経過から続けるときはシードを指定しなくても前と同じものを使う
+    let checkpoint_path = |i: usize| args.checkpoint.checkpoint_dir.as_ref().map(|dir| dir.join(format!("run-{}.json", i)));
+    let resumed_seed = checkpoint_path(0)
+        .filter(|path| path.exists())
+        .map(|path| Checkpoint::load(path).unwrap_or_else(|e| fail(&e)).seed);
+    let seed = args.seed.or(resumed_seed).unwrap_or_else(rand::random);
+    eprintln!("seed: {}", seed);
+    if let Some(dir) = &args.checkpoint.checkpoint_dir {
+        fs::create_dir_all(dir).unwrap_or_else(|e| fail(&format!("{}: {}", dir.display(), e)));
+    }
+    let mut simus: Vec<ViterbiSimu> = args.decoders
+        .iter()
+        .enumerate()
+        .map(|(i, decoder)| {
+            let mut vs = ViterbiSimu::new(decoder.clone(), code.clone(), args.start, args.step, args.end,
+                                          args.frame_len, args.frames)
+                .with_unit(args.unit)
+                .with_modulation(args.modulation)
+                .with_confidence(args.confidence)
+                .with_seed(seed);
+            if let Some(puncturer) = &puncturer {
+                vs = vs.with_puncturer(puncturer.clone());
+            }
+            if let Some(max_errors) = args.max_errors {
+                vs = vs.with_max_errors(max_errors);
+            }
+            if let Some(threads) = args.threads {
+                vs = vs.with_threads(threads);
+            }
+            match checkpoint_path(i) {
+                Some(path) => checkpoint::attach(vs, &path, args.checkpoint.every()).unwrap_or_else(|e| fail(&e)),
+                None => vs,
+            }
+        })
+        .collect();
+    for vs in &mut simus {
+        vs.simu();
+        vs.bit_per_error();
+    }
+
+    let simus: Vec<&ViterbiSimu> = simus.iter().collect();
+    write_results(&args.output, &simus);
+    plot_results(&args.plot, &simus);
+}
+
+fn table(vs: &ViterbiSimu) -> String {
+    let puncture = vs.puncturer.as_ref().map_or(String::new(), |p| format!(" puncture {}", p.spec()));
+    let mut table = format!("# code {}{} decoder {} {} seed {}\n# {} ber errors bits fer frame_errors frames events wilson_low wilson_high cp_low cp_high\n",
+                            vs.code.spec(), puncture, vs.way, vs.modulation, vs.seed, vs.unit.label());
+    for p in &vs.points {
+        table += &format!("{} {:e} {} {} {:e} {} {} {} {:e} {:e} {:e} {:e}\n",
+                          p.snr, p.ber, p.errors, p.bits, p.fer, p.frame_errors, p.frames, p.events,
+                          p.wilson.0, p.wilson.1, p.clopper_pearson.0, p.clopper_pearson.1);
+    }
+    table
+}
+
+// 拡張子が .csv か .json ならその形式、それ以外は表で書く
+fn write_results(path: &Option<PathBuf>, simus: &[&ViterbiSimu]) {
+    let reports = || simus.iter().map(|vs| Report::new(vs)).collect::<Vec<_>>();
+    let text = match path.as_ref().and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+        Some("csv") => export::to_csv(&reports()),
+        Some("json") => export::to_json(&reports()) + "\n",
+        _ => simus.iter().map(|vs| table(vs)).collect::<Vec<_>>().join("\n"),
+    };
+    write_output(path, &text);
+}
+
+// 1つなら理論値と一緒に、いくつもあれば重ねて描く
+fn plot_results(args: &PlotArgs, simus: &[&ViterbiSimu]) {
+    if !args.plot && args.plot_file.is_none() {
+        return;
+    }
+    let mut fg = match simus {
+        [vs] => plot::ber_figure(vs, args.error_bars),
+        _ => plot::comparison_figure(simus, args.error_bars).unwrap_or_else(|e| fail(&e)),
+    };
+    if let Some(path) = &args.plot_file {
+        plot::save(&mut fg, path).unwrap_or_else(|e| fail(&e));
+    }
+    if args.plot {
+        fg.show().unwrap_or_else(|e| fail(&format!("cant run gnuplot: {}", e)));
+    }
+}
+
+fn parse_bits(text: &str) -> Vec<u8> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '0' => 0,
+            '1' => 1,
+            _ => fail(&format!("input must be 0/1 bits, found {:?}", c)),
+        })
+        .collect()
+}
+
+fn encode(args: EncodeArgs) {
+    let code = args.code.code();
+    warn_catastrophic(&code, &args.code.code);
+    let mut encoder = Encoder::new(code, args.termination);
+    if let Some(puncturer) = args.code.puncturer() {
+        encoder = encoder.with_puncturer(puncturer);
+    }
+    let info = parse_bits(&read_input(&args.input));
+    if !info.len().is_multiple_of(encoder.code.k) {
+        fail(&format!("{} bits is not a multiple of {} inputs", info.len(), encoder.code.k));
+    }
+    let len = encoder.symbol_len(info.len());
+    if len < encoder.min_len() {
+        fail(&format!("{} bits is too short to encode (at least {} symbols, got {})", info.len(), encoder.min_len(), len));
+    }
+    let coded = encoder.encode(&info);
+    let text = match args.sigma {
+        Some(sigma) => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            eprintln!("seed: {}", seed);
+            let samples = Awgn::new(sigma, seed).transmit(&coded);
+            samples.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ")
+        }
+        None => coded.iter().map(|b| b.to_string()).collect(),
+    };
+    write_output(&args.output, &(text + "\n"));
+}
+
+fn decode(args: DecodeArgs) {
+    let entry = find_decoder(&args.decoder).unwrap_or_else(|| fail(&format!("unknown decoder {}", args.decoder)));
+    let code = args.code.code();
+    warn_catastrophic(&code, &args.code.code);
+    let trellis = Trellis::new(&code);
+    let mut encoder = Encoder::new(code, entry.termination);
+    if let Some(puncturer) = args.code.puncturer() {
+        if !entry.erasure {
+            fail(&format!("decoder {} cant handle punctured bits", entry.name));
+        }
+        encoder = encoder.with_puncturer(puncturer);
+    }
+    let received: Vec<f64> = read_input(&args.input)
+        .split_whitespace()
+        .map(|s| s.parse().unwrap_or_else(|e| fail(&format!("bad sample {}: {}", s, e))))
+        .collect();
+    let len = encoder.frame_len(received.len());
+    if encoder.sent_len(len) != received.len() {
+        fail(&format!("{} samples dont make whole symbols of code {}", received.len(), args.code.code));
+    }
+    if len < encoder.min_len() {
+        fail(&format!("{} symbols is too short for decoder {} (at least {})", len, entry.name, encoder.min_len()));
+    }
+    let mut decoder = (entry.build)(&trellis, args.sigma, args.seed.unwrap_or_else(rand::random));
+    let mut decoded = decoder.decode_samples(&encoder.depuncture(&received, len));
+    decoded.truncate(encoder.info_len(len));
+    let text: String = decoded.iter().map(|b| b.to_string()).collect();
+    write_output(&args.output, &(text + "\n"));
+}
+
+fn analyze(args: AnalyzeArgs) {
+    let code = args.code.code();
+    let rate = match args.code.puncturer() {
+        Some(puncturer) => puncturer.rate(&code),
+        None => code.rate(),
+    };
+    println!("rate: {}", rate);
+    println!("inputs: {}, outputs: {}, memory: {}, states: {}", code.k, code.n, code.memory(), code.num_states);
+    if let Some(gcd) = code.generator_gcd() {
+        println!("generator gcd: {:o}", gcd);
+    }
+    if code.is_catastrophic() {
+        println!("catastrophic: yes");
+        return;
+    }
+    println!("catastrophic: no");
+    if args.code.puncture.is_some() {
+        println!("(distance spectrum of the mother code)");
+    }
+    let spectrum = code.distance_spectrum(args.terms);
+    println!("free distance: {}", spectrum.free_distance);
+    println!("d A_d B_d");
+    for ((d, a), (_, b)) in spectrum.path_counts().iter().zip(spectrum.information_weights()) {
+        println!("{} {} {}", d, a, b);
+    }
+}
+
+fn campaign(args: CampaignArgs) {
+    let campaign = Campaign::from_file(&args.config).unwrap_or_else(|e| fail(&e));
+    let results = match &args.checkpoint.checkpoint_dir {
+        Some(dir) => campaign.run_with_checkpoints(dir, args.checkpoint.every()),
+        None => campaign.run(),
+    }
+    .unwrap_or_else(|e| fail(&e));
+    let simus: Vec<&ViterbiSimu> = results.iter().map(|(_, vs)| vs).collect();
+    write_results(&args.output, &simus);
+    plot_results(&args.plot, &simus);
+}
 
 fn main() {
-    let start_db = 1.0;
-    let tick_db = 0.5;
-    let end_db = 5.0;
-    let bits_len = 1024;
-    let iteration = 10000;
-
-    // let mut vs = ViterbiSimu::new("hard".to_string(), start_db, tick_db, end_db, bits_len, iteration);
-    let mut vs = ViterbiSimu::new("soft".to_string(), start_db, tick_db, end_db, bits_len, iteration);
-    vs.simu();
-    vs.bit_per_error();
-    dbg!(vs.oks);
-    dbg!(vs.ngs);
-
-    let mut fg = Figure::new();
-    fg.axes2d()
-      .set_title("Viterbi", &[])
-      .set_legend(Graph(0.5), Graph(0.9), &[], &[])
-      .set_x_label("SN", &[])
-      .set_y_label("log10(BER)", &[])
-      .points(
-          vs.ber.iter().map(|(i, _)| i),
-          vs.ber.iter().map(|(_, i)| i),
-          &[Caption("Parabola")],
-      );
-    // dbg!(bers);
-    // dbg!(vs);
-    fg.show().unwrap();
+    match Cli::parse().command {
+        Command::Simulate(args) => simulate(args),
+        Command::Encode(args) => encode(args),
+        Command::Decode(args) => decode(args),
+        Command::Analyze(args) => analyze(args),
+        Command::Campaign(args) => campaign(args),
+    }
 }
@@ -0,0 +1,52 @@
+use crate::binary;
+
+// 復号器に入れる受信シンボルの型
+// 受信信号 (0 -> -1, 1 -> 1 の bpsk) から作り、送ったビットとの距離 (小さいほど近い) を返す
+pub trait Metric: Copy + std::fmt::Debug {
+    fn from_sample(sample: f64) -> Self;
+    fn distance(self, bit: u8) -> f64;
+}
+
+// 硬判定したビット (ハミング距離)
+impl Metric for u8 {
+    fn from_sample(sample: f64) -> Self {
+        (sample > 0.) as u8
+    }
+
+    fn distance(self, bit: u8) -> f64 {
+        (self != bit) as u8 as f64
+    }
+}
+
+// 軟判定 (ユークリッド距離の2乗)
+impl Metric for f64 {
+    fn from_sample(sample: f64) -> Self {
+        sample
+    }
+
+    fn distance(self, bit: u8) -> f64 {
+        (binary::bpsk(bit) - self).powi(2)
+    }
+}
+
+// -7..=7 に量子化した軟判定 (相関)
+// 0 はどちらのビットとも同じ距離になるので、パンクチャの erasure に使える
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Quantized(pub i8);
+
+impl Quantized {
+    pub const MAX: i8 = 7;
+    // 受信信号 1.0 が何段になるか
+    pub const SCALE: f64 = 4.;
+}
+
+impl Metric for Quantized {
+    fn from_sample(sample: f64) -> Self {
+        let max = Quantized::MAX as f64;
+        Quantized((sample * Quantized::SCALE).round().clamp(-max, max) as i8)
+    }
+
+    fn distance(self, bit: u8) -> f64 {
+        (Quantized::MAX as f64) - self.0 as f64 * binary::bpsk(bit)
+    }
+}
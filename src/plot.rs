@@ -0,0 +1,305 @@
+// BER 曲線を gnuplot で描く
+// 画面に出す (Figure::show) ほか、端末がなくても PNG / SVG / PDF に書き出せる
+
+use std::path::Path;
+use std::str::FromStr;
+
+use gnuplot::{Axes2D, AxesCommon, Caption, Color, DashType, Figure, Graph, LineStyle, PointSize, PointSymbol};
+
+use crate::simu::ViterbiSimu;
+use crate::snr::{Modulation, SnrUnit};
+use crate::theory;
+
+// 曲線ごとに順に使う印と色
+const MARKERS: &[char] = &['O', 'S', 'T', 'D', 'R', 'o', 's', 't', 'd', 'r'];
+const COLORS: &[&str] = &[
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+// BER の点につける誤差棒 (信頼区間)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorBars {
+    Off,
+    Wilson,
+    ClopperPearson,
+}
+
+impl FromStr for ErrorBars {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ErrorBars::Off),
+            "wilson" => Ok(ErrorBars::Wilson),
+            "clopper-pearson" => Ok(ErrorBars::ClopperPearson),
+            _ => Err(format!("unknown error bars {} (off, wilson, clopper-pearson)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlotFormat {
+    Png,
+    Svg,
+    Pdf,
+}
+
+impl PlotFormat {
+    // 拡張子から決める
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "png" => Some(PlotFormat::Png),
+            "svg" => Some(PlotFormat::Svg),
+            "pdf" => Some(PlotFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+// 凡例に出す名前 (復号器と符号、BPSK 以外なら変調方式)
+pub fn label(vs: &ViterbiSimu) -> String {
+    let puncture = vs.puncturer.as_ref().map_or(String::new(), |p| format!(" punctured {}", p.spec()));
+    let modulation = match vs.modulation {
+        Modulation::Bpsk => String::new(),
+        modulation => format!(" {}", modulation),
+    };
+    format!("{} {}{}{}", vs.way, vs.code.spec(), puncture, modulation)
+}
+
+// label が同じになるものがあれば、違っている SN の範囲や止める条件を足して区別する
+fn labels(simus: &[&ViterbiSimu]) -> Vec<String> {
+    simus.iter()
+         .map(|vs| {
+             let mut text = label(vs);
+             let same: Vec<&&ViterbiSimu> = simus.iter().filter(|other| label(other) == text).collect();
+             if same.iter().any(|o| (o.start_db, o.tick_db, o.end_db) != (vs.start_db, vs.tick_db, vs.end_db)) {
+                 text += &format!(" {} to {} by {}", vs.start_db, vs.end_db, vs.tick_db);
+             }
+             if same.iter().any(|o| o.bits_len != vs.bits_len) {
+                 text += &format!(" frame {}", vs.bits_len);
+             }
+             if same.iter().any(|o| (o.iteration, o.max_errors) != (vs.iteration, vs.max_errors)) {
+                 text += &format!(" frames {}", vs.iteration);
+                 if let Some(max_errors) = vs.max_errors {
+                     text += &format!(" max errors {}", max_errors);
+                 }
+             }
+             text
+         })
+         .collect()
+}
+
+// simus の全ての点の (unit の SN, Eb/N0) を SN の順に重ならないように並べる
+fn snr_points(simus: &[&ViterbiSimu]) -> Vec<(f64, f64)> {
+    let mut points: Vec<(f64, f64)> = simus.iter().flat_map(|vs| vs.points.iter().map(|p| (p.snr, p.ebn0))).collect();
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    points.dedup_by(|a, b| a.0 == b.0);
+    points
+}
+
+fn new_axes(fg: &mut Figure, unit: SnrUnit) -> &mut Axes2D {
+    let axes = fg.axes2d();
+    axes.set_title("Viterbi", &[])
+        .set_legend(Graph(0.95), Graph(0.95), &[], &[])
+        .set_x_label(unit.label(), &[])
+        .set_y_label("BER", &[])
+        .set_y_log(Some(10.))
+        .set_grid_options(false, &[LineStyle(DashType::Dot)])
+        .set_x_grid(true)
+        .set_y_grid(true);
+    axes
+}
+
+// index 番目の曲線として vs の BER を描く
+fn draw_curve(axes: &mut Axes2D, vs: &ViterbiSimu, caption: &str, index: usize, error_bars: ErrorBars) {
+    let marker = MARKERS[index % MARKERS.len()];
+    let color = COLORS[index % COLORS.len()];
+    axes.lines_points(
+        vs.ber.iter().map(|(snr, _)| snr),
+        vs.ber.iter().map(|(_, ber)| ber),
+        &[Caption(caption), PointSymbol(marker), Color(color)],
+    );
+    // 誤りのない点は区間の下限が0で対数の軸に描けない
+    let intervals: Vec<(f64, (f64, f64))> = vs.points
+        .iter()
+        .filter(|p| p.errors > 0)
+        .filter_map(|p| match error_bars {
+            ErrorBars::Off => None,
+            ErrorBars::Wilson => Some((p.snr, p.wilson)),
+            ErrorBars::ClopperPearson => Some((p.snr, p.clopper_pearson)),
+        })
+        .collect();
+    if !intervals.is_empty() {
+        // 区間の真ん中に見えない点を置いて、上下に半分の幅の棒を出す
+        axes.y_error_bars(
+            intervals.iter().map(|(snr, _)| *snr),
+            intervals.iter().map(|(_, (low, high))| (low + high) / 2.),
+            intervals.iter().map(|(_, (low, high))| (high - low) / 2.),
+            &[Caption(""), PointSize(0.), Color(color)],
+        );
+    }
+}
+
+// 軟判定の和集合上界を一緒に描くか
+// パンクチャした符号の距離スペクトルは元の符号と違い、硬判定や量子化した復号器は軟判定の上界より悪くなるので描かない
+fn has_union_bound(vs: &ViterbiSimu) -> bool {
+    vs.puncturer.is_none() && vs.decoder().soft_metric
+}
+
+// bit_per_error の後の vs の BER と、符号化しない BPSK・軟判定の和集合上界の曲線 (縦軸は対数)
+pub fn ber_figure(vs: &ViterbiSimu, error_bars: ErrorBars) -> Figure {
+    let snrs: Vec<f64> = vs.points.iter().map(|p| p.snr).collect();
+    let uncoded: Vec<f64> = vs.points.iter().map(|p| theory::uncoded_bpsk(p.ebn0)).collect();
+
+    let mut fg = Figure::new();
+    let axes = new_axes(&mut fg, vs.unit);
+    draw_curve(axes, vs, &label(vs), 0, error_bars);
+    axes.lines(&snrs, &uncoded, &[Caption("uncoded BPSK"), LineStyle(DashType::Dash), Color("black")]);
+    if has_union_bound(vs) {
+        let weights = vs.code.distance_spectrum(10).information_weights();
+        let bound: Vec<f64> = vs.points
+            .iter()
+            .map(|p| theory::union_bound(p.ebn0, vs.frame_rate(), vs.code.k, &weights))
+            .collect();
+        axes.lines(&snrs, &bound, &[Caption("soft-decision union bound"), LineStyle(DashType::DotDash), Color("black")]);
+    }
+    fg
+}
+
+// いくつもの設定の BER を重ねて描く (横軸の単位はそろっていないといけない)
+// 符号化しない BPSK は同じ Eb/N0 で比べるので、横軸が Eb/N0 でなければ符号化率と変調方式ごとに描く
+// 軟判定の和集合上界はパンクチャしていない符号ごとに、軟判定で復号した最初の曲線の色で描く
+pub fn comparison_figure(simus: &[&ViterbiSimu], error_bars: ErrorBars) -> Result<Figure, String> {
+    let unit = simus.first().ok_or("nothing to plot")?.unit;
+    if let Some(other) = simus.iter().find(|vs| vs.unit != unit) {
+        return Err(format!("cant plot {} and {} on the same axis", unit.label(), other.unit.label()));
+    }
+    let mut fg = Figure::new();
+    let axes = new_axes(&mut fg, unit);
+    for (index, (vs, caption)) in simus.iter().zip(labels(simus)).enumerate() {
+        draw_curve(axes, vs, &caption, index, error_bars);
+    }
+
+    // Eb/N0 なら SN から Eb/N0 が1つに決まるので、変調方式は区別しない
+    let modulation = |vs: &ViterbiSimu| if unit == SnrUnit::EbN0 { None } else { Some(vs.modulation) };
+    let mut uncoded_groups: Vec<(Option<f64>, Option<Modulation>)> = Vec::new();
+    for vs in simus {
+        let key = (if unit == SnrUnit::EbN0 { None } else { Some(vs.frame_rate()) }, modulation(vs));
+        if !uncoded_groups.contains(&key) {
+            uncoded_groups.push(key);
+        }
+    }
+    for (rate, modulation) in &uncoded_groups {
+        let group: Vec<&ViterbiSimu> = simus
+            .iter()
+            .filter(|vs| rate.is_none_or(|r| vs.frame_rate() == r) && modulation.is_none_or(|m| vs.modulation == m))
+            .copied()
+            .collect();
+        let points = snr_points(&group);
+        let uncoded: Vec<f64> = points.iter().map(|(_, ebn0)| theory::uncoded_bpsk(*ebn0)).collect();
+        let caption = match (rate, modulation) {
+            (Some(rate), Some(modulation)) if uncoded_groups.len() > 1 => {
+                format!("uncoded BPSK (same Eb/N0 as rate {:.3} {})", rate, modulation)
+            }
+            _ => "uncoded BPSK".to_string(),
+        };
+        axes.lines(points.iter().map(|(snr, _)| snr), &uncoded,
+                   &[Caption(&caption), LineStyle(DashType::Dash), Color("black")]);
+    }
+
+    let mut bound_groups: Vec<(usize, String, Option<Modulation>)> = Vec::new();
+    for (index, vs) in simus.iter().enumerate() {
+        let key = (vs.code.spec(), modulation(vs));
+        if has_union_bound(vs) && !bound_groups.iter().any(|(_, spec, m)| (spec, m) == (&key.0, &key.1)) {
+            bound_groups.push((index, key.0, key.1));
+        }
+    }
+    for (index, spec, modulation) in &bound_groups {
+        let first = simus[*index];
+        let group: Vec<&ViterbiSimu> = simus
+            .iter()
+            .filter(|vs| has_union_bound(vs) && vs.code.spec() == *spec)
+            .filter(|vs| modulation.is_none_or(|m| vs.modulation == m))
+            .copied()
+            .collect();
+        let weights = first.code.distance_spectrum(10).information_weights();
+        let points = snr_points(&group);
+        let bound: Vec<f64> = points
+            .iter()
+            .map(|(_, ebn0)| theory::union_bound(*ebn0, first.frame_rate(), first.code.k, &weights))
+            .collect();
+        let caption = match modulation {
+            Some(modulation) => format!("soft-decision union bound {} {}", spec, modulation),
+            None => format!("soft-decision union bound {}", spec),
+        };
+        axes.lines(points.iter().map(|(snr, _)| snr), &bound,
+                   &[Caption(&caption), LineStyle(DashType::DotDash), Color(COLORS[index % COLORS.len()])]);
+    }
+    Ok(fg)
+}
+
+// 拡張子の形式で path に書き出す
+pub fn save(fg: &mut Figure, path: &Path) -> Result<(), String> {
+    let format = PlotFormat::from_path(path)
+        .ok_or_else(|| format!("{}: plot must be .png, .svg or .pdf", path.display()))?;
+    let file = path.to_str().ok_or_else(|| format!("{}: path is not utf-8", path.display()))?;
+    let saved = match format {
+        PlotFormat::Png => fg.save_to_png(file, 800, 600),
+        PlotFormat::Svg => fg.save_to_svg(file, 800, 600),
+        // インチ
+        PlotFormat::Pdf => fg.save_to_pdf(file, 8, 6),
+    };
+    saved.map_err(|e| format!("cant run gnuplot: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::ConvolutionalCode;
+    use crate::simu::test_simu;
+
+    fn simu(way: &str, code: ConvolutionalCode, end_db: f64, frames: usize) -> ViterbiSimu {
+        let mut vs = test_simu(way, code, (1., 1., end_db), 64, frames);
+        vs.simu();
+        vs.bit_per_error();
+        vs
+    }
+
+    #[test]
+    fn labels_tell_runs_apart() {
+        let a = simu("soft", ConvolutionalCode::k3(), 2., 100);
+        let b = simu("soft", ConvolutionalCode::k3(), 3., 100);
+        let c = simu("hard", ConvolutionalCode::k3(), 3., 200);
+        let labels = labels(&[&a, &b, &c]);
+        assert_eq!(labels, vec!["soft 3:7,5 1 to 2 by 1", "soft 3:7,5 1 to 3 by 1", "hard 3:7,5"]);
+    }
+
+    #[test]
+    fn comparison_draws_union_bound_per_code() {
+        let k3_soft = simu("soft", ConvolutionalCode::k3(), 2., 100);
+        let k3_hard = simu("hard", ConvolutionalCode::k3(), 2., 100);
+        let nasa = simu("soft", ConvolutionalCode::nasa(), 2., 100);
+        let fg = comparison_figure(&[&k3_soft, &k3_hard, &nasa], ErrorBars::Off).unwrap();
+        let path = std::env::temp_dir().join(format!("rust_viterbi_plot_{}.gp", std::process::id()));
+        fg.echo_to_file(path.to_str().unwrap());
+        let script = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(script.matches("t \"uncoded BPSK\"").count(), 1);
+        assert_eq!(script.matches("t \"soft-decision union bound 3:7,5\"").count(), 1);
+        assert_eq!(script.matches("t \"soft-decision union bound 7:171,133\"").count(), 1);
+    }
+
+    #[test]
+    fn no_union_bound_for_hard_decisions() {
+        let hard = simu("hard", ConvolutionalCode::k3(), 2., 100);
+        let quantized = simu("soft-quantized", ConvolutionalCode::k3(), 2., 100);
+        for fg in [ber_figure(&hard, ErrorBars::Off), comparison_figure(&[&hard, &quantized], ErrorBars::Off).unwrap()] {
+            let path = std::env::temp_dir().join(format!("rust_viterbi_plot_hard_{}.gp", std::process::id()));
+            fg.echo_to_file(path.to_str().unwrap());
+            let script = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+            std::fs::remove_file(&path).unwrap();
+            assert!(script.contains("t \"uncoded BPSK\""));
+            assert!(!script.contains("union bound"));
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::code::ConvolutionalCode;
+
+// パンクチャ行列
+// pattern[j][t] が 0 なら、周期中 t 番目の入力シンボルに対する出力 j を送らない
+#[derive(Debug, Clone, PartialEq)]
+pub struct Puncturer {
+    pub pattern: Vec<Vec<u8>>,
+}
+
+impl Puncturer {
+    pub fn new(pattern: Vec<Vec<u8>>) -> Self {
+        if pattern.is_empty() || pattern[0].is_empty() {
+            panic!("cant make empty puncturing pattern");
+        }
+        let period = pattern[0].len();
+        if pattern.iter().any(|row| row.len() != period || row.iter().any(|p| *p > 1)) {
+            panic!("invalid puncturing pattern: {:?}", pattern);
+        }
+        if (0..period).all(|t| pattern.iter().all(|row| row[t] == 0)) {
+            panic!("puncturing pattern sends nothing: {:?}", pattern);
+        }
+        Puncturer { pattern }
+    }
+
+    // IEEE 802.11a/g/n (mother code K=7 (133, 171) = ConvolutionalCode::ieee80211)
+    // nasa (171, 133) に使うと行が逆の出力にかかる
+    pub fn ieee80211(rate: &str) -> Self {
+        match rate {
+            "2/3" => Puncturer::new(vec![vec![1, 1], vec![1, 0]]),
+            "3/4" => Puncturer::new(vec![vec![1, 1, 0], vec![1, 0, 1]]),
+            "5/6" => Puncturer::new(vec![vec![1, 1, 0, 1, 0], vec![1, 0, 1, 0, 1]]),
+            _ => panic!("802.11 has no puncturing pattern for rate {}", rate),
+        }
+    }
+
+    // DVB-S (mother code K=7 (171, 133) = ConvolutionalCode::nasa)
+    pub fn dvb(rate: &str) -> Self {
+        match rate {
+            "2/3" => Puncturer::new(vec![vec![1, 0], vec![1, 1]]),
+            "3/4" => Puncturer::new(vec![vec![1, 0, 1], vec![1, 1, 0]]),
+            "5/6" => Puncturer::new(vec![vec![1, 0, 1, 0, 1], vec![1, 1, 0, 1, 0]]),
+            "7/8" => Puncturer::new(vec![vec![1, 0, 0, 0, 1, 0, 1], vec![1, 1, 1, 1, 0, 1, 0]]),
+            _ => panic!("DVB has no puncturing pattern for rate {}", rate),
+        }
+    }
+
+    // "ieee80211:3/4", "dvb:7/8" か、行を ";" で区切ったパターン (例 "110;101")
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if let Some((standard, rate)) = spec.split_once(':') {
+            let patterns: &[&str] = match standard {
+                "ieee80211" => &["2/3", "3/4", "5/6"],
+                "dvb" => &["2/3", "3/4", "5/6", "7/8"],
+                _ => return Err(format!("unknown puncturing standard {}", standard)),
+            };
+            if !patterns.contains(&rate) {
+                return Err(format!("{} has no puncturing pattern for rate {}", standard, rate));
+            }
+            return Ok(if standard == "dvb" { Puncturer::dvb(rate) } else { Puncturer::ieee80211(rate) });
+        }
+        let pattern: Vec<Vec<u8>> = spec
+            .split(';')
+            .map(|row| {
+                row.trim()
+                   .chars()
+                   .map(|c| match c {
+                       '0' => Ok(0),
+                       '1' => Ok(1),
+                       _ => Err(format!("bad puncturing pattern {}", spec)),
+                   })
+                   .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        let period = pattern[0].len();
+        if period == 0 || pattern.iter().any(|row| row.len() != period) || pattern.iter().flatten().all(|p| *p == 0) {
+            return Err(format!("bad puncturing pattern {}", spec));
+        }
+        Ok(Puncturer::new(pattern))
+    }
+
+    // parse で読めるパターンの書き方
+    pub fn spec(&self) -> String {
+        self.pattern
+            .iter()
+            .map(|row| row.iter().map(|p| p.to_string()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    pub fn period(&self) -> usize {
+        self.pattern[0].len()
+    }
+
+    // 1周期で送るビット数
+    pub fn kept(&self) -> usize {
+        self.pattern.iter().flatten().filter(|p| **p == 1).count()
+    }
+
+    // パンクチャ後の符号化率
+    pub fn rate(&self, code: &ConvolutionalCode) -> f64 {
+        if self.pattern.len() != code.n {
+            panic!("puncturing pattern has {} rows but code has {} outputs", self.pattern.len(), code.n);
+        }
+        (code.k * self.period()) as f64 / self.kept() as f64
+    }
+
+    // coded_len ビットのうち送るビット数
+    // 周期ごとに kept() ビットなので、数えるのは最後の半端な周期だけ
+    pub fn punctured_len(&self, coded_len: usize) -> usize {
+        let period_len = self.pattern.len() * self.period();
+        let rest = coded_len % period_len;
+        self.kept() * (coded_len / period_len) + (0..rest).filter(|i| self.keeps(*i)).count()
+    }
+
+    fn keeps(&self, index: usize) -> bool {
+        let n = self.pattern.len();
+        self.pattern[index % n][(index / n) % self.period()] == 1
+    }
+
+    pub fn puncture<T: Copy>(&self, coded: &[T]) -> Vec<T> {
+        coded.iter()
+             .enumerate()
+             .filter(|(i, _)| self.keeps(*i))
+             .map(|(_, c)| *c)
+             .collect()
+    }
+
+    // 送らなかったビットの位置に erasure (どちらの値とも同じ距離になる0) を入れて、
+    // 符号化後の coded_len ビットの長さに戻す
+    pub fn depuncture(&self, received: &[f64], coded_len: usize) -> Vec<f64> {
+        let mut received = received.iter();
+        let depunctured: Vec<f64> = (0..coded_len)
+            .map(|i| if self.keeps(i) { *received.next().expect("too few received symbols") } else { 0. })
+            .collect();
+        if received.next().is_some() {
+            panic!("too many received symbols for {} coded bits", coded_len);
+        }
+        depunctured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_rates() {
+        let ieee80211 = ConvolutionalCode::ieee80211();
+        for (rate, expected) in [("2/3", 2. / 3.), ("3/4", 3. / 4.), ("5/6", 5. / 6.)] {
+            assert_eq!(Puncturer::ieee80211(rate).rate(&ieee80211), expected);
+        }
+        let nasa = ConvolutionalCode::nasa();
+        for (rate, expected) in [("2/3", 2. / 3.), ("3/4", 3. / 4.), ("5/6", 5. / 6.), ("7/8", 7. / 8.)] {
+            assert_eq!(Puncturer::dvb(rate).rate(&nasa), expected);
+        }
+    }
+
+    #[test]
+    fn depuncture_puts_erasures_back() {
+        for puncturer in [Puncturer::ieee80211("3/4"), Puncturer::dvb("7/8"), Puncturer::parse("110;101").unwrap()] {
+            // 周期の途中で終わる長さも試す
+            let coded: Vec<f64> = (0..50).map(|i| if i % 3 == 0 { 1. } else { -1. }).collect();
+            let punctured = puncturer.puncture(&coded);
+            assert_eq!(punctured.len(), puncturer.punctured_len(coded.len()));
+            let depunctured = puncturer.depuncture(&punctured, coded.len());
+            for (i, (c, d)) in coded.iter().zip(&depunctured).enumerate() {
+                assert_eq!(*d, if puncturer.keeps(i) { *c } else { 0. });
+            }
+        }
+    }
+
+    #[test]
+    fn punctured_len_counts_kept_bits() {
+        for puncturer in [Puncturer::ieee80211("5/6"), Puncturer::dvb("7/8"), Puncturer::parse("110;101").unwrap()] {
+            for coded_len in 0..60 {
+                let kept = (0..coded_len).filter(|i| puncturer.keeps(*i)).count();
+                assert_eq!(puncturer.punctured_len(coded_len), kept);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_round_trips_spec() {
+        for spec in ["ieee80211:2/3", "dvb:7/8", "110;101"] {
+            let puncturer = Puncturer::parse(spec).unwrap();
+            assert_eq!(Puncturer::parse(&puncturer.spec()).unwrap(), puncturer);
+        }
+        assert!(Puncturer::parse("ieee80211:7/8").is_err());
+        assert!(Puncturer::parse("110;10").is_err());
+    }
+}
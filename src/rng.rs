@@ -0,0 +1,62 @@
+use std::fmt;
+
+use rand_core::{Error, RngCore, SeedableRng};
+use sfmt::SFMT;
+
+// シードを覚えている SFMT
+#[derive(Clone)]
+pub struct SeededRng {
+    pub seed: u64,
+    rng: SFMT,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            seed,
+            rng: SFMT::seed_from_u64(seed),
+        }
+    }
+}
+
+impl fmt::Debug for SeededRng {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeededRng").field("seed", &self.seed).finish()
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// splitmix64
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// マスターシードと stream (SN の番号や用途など) から、独立した乱数列のシードを作る
+pub fn derive(seed: u64, stream: &[u64]) -> u64 {
+    stream.iter().fold(mix(seed), |acc, s| mix(acc ^ mix(*s)))
+}
+
+// 乱数列の用途
+pub const SOURCE: u64 = 0;
+pub const NOISE: u64 = 1;
+pub const DECODER: u64 = 2;
@@ -0,0 +1,464 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::channel::{Awgn, Channel};
+use crate::checkpoint::Checkpoint;
+use crate::code::ConvolutionalCode;
+use crate::encoder::Encoder;
+use crate::error_stats::ErrorStats;
+use crate::puncture::Puncturer;
+use crate::rng;
+use crate::snr::{self, Modulation, SnrUnit};
+use crate::source::Source;
+use crate::stats;
+use crate::trellis::Trellis;
+use crate::viterbi::{find_decoder, DecoderEntry, DECODERS};
+
+// 1つの乱数列で続けて送るフレーム数
+// スレッド数によらず同じ結果になるように、この単位で乱数列を分けてスレッドに配る
+pub const BATCH: usize = 100;
+
+// 1つの SN 点の結果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BerPoint {
+    // 掃引した値 (unit の単位)
+    pub snr: f64,
+    pub ebn0: f64,
+    pub esn0: f64,
+    pub bits: usize,
+    pub errors: usize,
+    pub frames: usize,
+    pub frame_errors: usize,
+    pub events: usize,
+    // 誤りイベントの平均の長さ
+    pub mean_burst: f64,
+    // ErrorStats と同じく bursts[l] は長さ l ビットの誤りイベントの数、positions[i] はフレームの i ビット目が誤った回数
+    // (CSV には入れない)
+    pub bursts: Vec<usize>,
+    pub positions: Vec<usize>,
+    pub ber: f64,
+    pub fer: f64,
+    // 信頼度 confidence の BER の区間 (下限, 上限)
+    pub wilson: (f64, f64),
+    pub clopper_pearson: (f64, f64),
+}
+
+// ViterbiSimu::new と with_* は設定がおかしいと panic するので、ファイルやコマンドラインから読んだ設定は先にこれで確かめる
+pub fn check_settings(code: &ConvolutionalCode, decoder: &str, puncturer: Option<&Puncturer>,
+                      (start_db, tick_db, end_db): (f64, f64, f64), bits_len: usize, confidence: f64)
+                      -> Result<(), String> {
+    if code.is_catastrophic() {
+        return Err(format!("code {} is catastrophic", code.spec()));
+    }
+    let entry = find_decoder(decoder).ok_or_else(|| {
+        let names: Vec<&str> = DECODERS.iter().map(|d| d.name).collect();
+        format!("unknown decoder {} ({})", decoder, names.join(", "))
+    })?;
+    if let Some(puncturer) = puncturer {
+        if !entry.erasure {
+            return Err(format!("decoder {} cant handle punctured bits", decoder));
+        }
+        if puncturer.pattern.len() != code.n {
+            return Err(format!("puncturing pattern {} doesnt fit code {}", puncturer.spec(), code.spec()));
+        }
+    }
+    if !(tick_db > 0. && start_db.is_finite() && end_db.is_finite() && end_db >= start_db) {
+        return Err(format!("bad SNR range {} to {} by {}", start_db, end_db, tick_db));
+    }
+    let min_len = Encoder::new(code.clone(), entry.termination).min_len();
+    if bits_len < min_len {
+        return Err(format!("frame length {} is too short for decoder {} (at least {})", bits_len, decoder, min_len));
+    }
+    if !(confidence > 0. && confidence < 1.) {
+        return Err(format!("confidence must be in (0, 1): {}", confidence));
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct ViterbiSimu {
+    pub way: String,
+    pub code: ConvolutionalCode,
+    pub puncturer: Option<Puncturer>,
+    // パンクチャ後の符号化率 (終端のビットは数えない。SN の換算には frame_rate を使う)
+    pub rate: f64,
+    pub len: usize,
+    // start_db, tick_db, end_db の単位
+    pub unit: SnrUnit,
+    pub modulation: Modulation,
+    pub start_db: f64,
+    pub tick_db: f64,
+    pub end_db: f64,
+    pub bits_len: usize,
+    // 1つの SN で送る最大のフレーム数
+    pub iteration: usize,
+    // この数だけビット誤りが出たらその SN は打ち切る
+    pub max_errors: Option<usize>,
+    pub confidence: f64,
+    // 全ての乱数はこのシードから作る
+    pub seed: u64,
+    pub threads: usize,
+    // (unit の SN [dB], BER) 誤りが1つもなかった点は対数の軸に描けないので入れない
+    pub ber: Vec<(f64, f64)>,
+    pub points: Vec<BerPoint>,
+    pub oks: Vec<usize>,
+    pub ngs: Vec<usize>,
+    pub stats: Vec<ErrorStats>,
+    // 各 SN で次に使うバッチの番号
+    pub next_batch: Vec<usize>,
+    // simu にかかった時間 (続きから回したときは前の分も足す)
+    pub elapsed: Duration,
+    // 途中経過を残すファイルと間隔
+    pub checkpoint: Option<(PathBuf, Duration)>,
+}
+
+impl ViterbiSimu {
+    pub fn new(way: String,
+               code: ConvolutionalCode,
+               start_db: f64,
+               tick_db: f64,
+               end_db: f64,
+               bits_len: usize,
+               iteration: usize,
+    ) -> ViterbiSimu {
+        if code.is_catastrophic() {
+            panic!("code {:?} is catastrophic", code.generators);
+        }
+        if !(tick_db > 0. && end_db >= start_db) {
+            panic!("bad SNR range {} to {} by {}", start_db, end_db, tick_db);
+        }
+        let rate = code.rate();
+        // 0.1 刻みなどで end_db の点が丸め誤差で落ちないように少し足してから切り捨てる
+        let len = ((end_db - start_db) / tick_db + 1e-9).floor() as usize + 1;
+        ViterbiSimu {
+            way,
+            code,
+            puncturer: None,
+            rate,
+            len,
+            unit: SnrUnit::EbN0,
+            modulation: Modulation::Bpsk,
+            start_db,
+            tick_db,
+            end_db,
+            bits_len,
+            iteration,
+            max_errors: None,
+            confidence: 0.95,
+            seed: rand::random(),
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            ber: Vec::new(),
+            points: Vec::new(),
+            oks: vec![0; len],
+            ngs: vec![0; len],
+            stats: vec![ErrorStats::new(); len],
+            next_batch: vec![0; len],
+            elapsed: Duration::ZERO,
+            checkpoint: None,
+        }
+    }
+
+    pub fn decoder(&self) -> &'static DecoderEntry {
+        find_decoder(&self.way).unwrap_or_else(|| panic!("i don't know decoder {}", self.way))
+    }
+
+    pub fn with_unit(mut self, unit: SnrUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn with_modulation(mut self, modulation: Modulation) -> Self {
+        self.modulation = modulation;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // N ビット誤りが出るか iteration フレーム送ったら止める
+    // 誤りの数は BATCH フレームごとにしか見ないので、SN が低いと1バッチ分の誤りだけ N を超えることがある
+    pub fn with_max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = Some(max_errors);
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        if !(confidence > 0. && confidence < 1.) {
+            panic!("confidence must be in (0, 1): {}", confidence);
+        }
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_puncturer(mut self, puncturer: Puncturer) -> Self {
+        if !self.decoder().erasure {
+            panic!("decoder {} cant handle punctured bits", self.way);
+        }
+        self.rate = puncturer.rate(&self.code);
+        self.puncturer = Some(puncturer);
+        self
+    }
+
+    // simu の間 every ごとと終わりに path に途中経過を書く
+    pub fn with_checkpoint<P: AsRef<Path>>(mut self, path: P, every: Duration) -> Self {
+        self.checkpoint = Some((path.as_ref().to_path_buf(), every));
+        self
+    }
+
+    // 途中経過から続ける (同じ設定で取ったものでないといけない)
+    pub fn resume(&mut self, checkpoint: &Checkpoint) {
+        if let Err(e) = checkpoint.check(self) {
+            panic!("{}", e);
+        }
+        self.stats = checkpoint.stats.clone();
+        self.oks = self.stats.iter().map(|s| s.bits - s.errors).collect();
+        self.ngs = self.stats.iter().map(|s| s.errors).collect();
+        self.next_batch = checkpoint.next_batch.clone();
+        self.elapsed = Duration::from_secs_f64(checkpoint.elapsed_secs);
+    }
+
+    fn save_checkpoint(&self, path: &Path) {
+        if let Err(e) = Checkpoint::new(self).save(path) {
+            panic!("cant write checkpoint {}", e);
+        }
+    }
+
+    fn finished(&self, point: usize) -> bool {
+        let enough_errors = self.max_errors.is_some_and(|max| self.ngs[point] >= max);
+        enough_errors || self.stats[point].frames >= self.iteration
+    }
+
+    fn encoder(&self, entry: &DecoderEntry) -> Encoder {
+        let encoder = Encoder::new(self.code.clone(), entry.termination);
+        match &self.puncturer {
+            Some(puncturer) => encoder.with_puncturer(puncturer.clone()),
+            None => encoder,
+        }
+    }
+
+    fn db(&self, point: usize) -> f64 {
+        self.start_db + point as f64 * self.tick_db
+    }
+
+    // 1フレームで送るビットあたりの情報ビット数
+    // 0で終端する復号器では終端の分だけ rate より下がるので、SN と Eb/N0 の換算にはこちらを使う
+    pub fn frame_rate(&self) -> f64 {
+        self.encoder(self.decoder()).frame_rate(self.bits_len)
+    }
+
+    fn sigma(&self, point: usize) -> f64 {
+        snr::sigma(self.db(point), self.unit, self.frame_rate(), self.modulation)
+    }
+
+    // SN の point 番目で batch 番目の乱数列を使って frames フレーム送る
+    fn run_batch(&self, entry: &DecoderEntry, trellis: &Trellis, encoder: &Encoder,
+                 point: usize, batch: usize, frames: usize) -> ErrorStats {
+        let sigma = self.sigma(point);
+        let stream = |kind| rng::derive(self.seed, &[point as u64, batch as u64, kind]);
+        let mut source = Source::new(stream(rng::SOURCE));
+        let mut channel = Awgn::new(sigma, stream(rng::NOISE));
+        let mut decoder = (entry.build)(trellis, sigma, stream(rng::DECODER));
+        // 拘束長分より離れた誤りは別の誤りイベントとする
+        let gap = self.code.constraint_length() * self.code.k;
+        let mut stats = ErrorStats::new();
+        for _ in 0..frames {
+            let raw_request_data = source.bits(encoder.info_len(self.bits_len));
+            let signal_request_data = encoder.encode(&raw_request_data);
+            let noised_request_data = encoder.depuncture(&channel.transmit(&signal_request_data), self.bits_len);
+            let raw_answer_data = decoder.decode_samples(&noised_request_data);
+            // 終端のビットは数えない
+            stats.add_frame(&raw_request_data, &raw_answer_data, gap);
+        }
+        stats
+    }
+
+    // units の (SN の番号, バッチの番号, フレーム数) を並列に送り、(SN の番号, バッチの番号, 集計) を返す
+    fn run_batches(&self, entry: &DecoderEntry, trellis: &Trellis, encoder: &Encoder,
+                   units: &[(usize, usize, usize)]) -> Vec<(usize, usize, ErrorStats)> {
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(units.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let u = next.fetch_add(1, Ordering::Relaxed);
+                            if u >= units.len() {
+                                break;
+                            }
+                            let (point, batch, frames) = units[u];
+                            done.push((point, batch, self.run_batch(entry, trellis, encoder, point, batch, frames)));
+                        }
+                        done
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        })
+    }
+
+    pub fn simu(&mut self) {
+        let started = Instant::now();
+        let elapsed = self.elapsed;
+        let mut saved = Instant::now();
+        let entry = self.decoder();
+        let trellis = Trellis::new(&self.code);
+        let encoder = self.encoder(entry);
+
+        // 各 SN について threads 個ずつ先のバッチを送り、バッチの順に足していく
+        // 止める条件を満たしたバッチより後ろは捨てるので、スレッド数によらず同じ結果になる
+        let mut finished: Vec<bool> = (0..self.len).map(|point| self.finished(point)).collect();
+        loop {
+            let units: Vec<(usize, usize, usize)> = (0..self.len)
+                .filter(|point| !finished[*point])
+                .flat_map(|point| {
+                    // 続きから回すときは前に送った分を引く
+                    let remaining = self.iteration - self.stats[point].frames;
+                    let next = self.next_batch[point];
+                    (0..self.threads)
+                        .filter(move |i| i * BATCH < remaining)
+                        .map(move |i| (point, next + i, BATCH.min(remaining - i * BATCH)))
+                })
+                .collect();
+            if units.is_empty() {
+                break;
+            }
+            let mut results = self.run_batches(entry, &trellis, &encoder, &units);
+            results.sort_by_key(|(point, batch, _)| (*point, *batch));
+            for (point, batch, stats) in results {
+                if finished[point] {
+                    continue;
+                }
+                self.oks[point] += stats.bits - stats.errors;
+                self.ngs[point] += stats.errors;
+                self.stats[point].merge(&stats);
+                self.next_batch[point] = batch + 1;
+                finished[point] = self.finished(point);
+            }
+            self.elapsed = elapsed + started.elapsed();
+            if let Some((path, every)) = &self.checkpoint {
+                if saved.elapsed() >= *every {
+                    self.save_checkpoint(path);
+                    saved = Instant::now();
+                }
+            }
+        }
+        self.elapsed = elapsed + started.elapsed();
+        if let Some((path, _)) = &self.checkpoint {
+            self.save_checkpoint(path);
+        }
+    }
+
+    pub fn bit_per_error(&mut self) {
+        let rate = self.frame_rate();
+        self.points = (0..self.len)
+            .map(|i| {
+                let stats = &self.stats[i];
+                let bits = stats.bits;
+                let errors = stats.errors;
+                let esn0 = snr::es_n0(self.db(i), self.unit, rate, self.modulation);
+                BerPoint {
+                    snr: self.db(i),
+                    ebn0: snr::eb_n0(esn0, rate, self.modulation),
+                    esn0,
+                    bits,
+                    errors,
+                    frames: stats.frames,
+                    frame_errors: stats.frame_errors,
+                    events: stats.events,
+                    mean_burst: stats.mean_burst(),
+                    bursts: stats.bursts.clone(),
+                    positions: stats.positions.clone(),
+                    ber: stats.ber(),
+                    fer: stats.fer(),
+                    wilson: stats::wilson(errors, bits, self.confidence),
+                    clopper_pearson: stats::clopper_pearson(errors, bits, self.confidence),
+                }
+            })
+            .collect();
+        self.ber = self.points
+            .iter()
+            .filter(|p| p.errors > 0)
+            .map(|p| (p.snr, p.ber))
+            .collect();
+    }
+}
+
+// テスト用のシミュレーション (シード 1、1スレッド)
+#[cfg(test)]
+pub(crate) fn test_simu(way: &str, code: ConvolutionalCode, (start_db, tick_db, end_db): (f64, f64, f64),
+                        bits_len: usize, frames: usize) -> ViterbiSimu {
+    ViterbiSimu::new(way.to_string(), code, start_db, tick_db, end_db, bits_len, frames).with_seed(1).with_threads(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_settings_rejects_what_new_would_panic_on() {
+        let k3 = ConvolutionalCode::k3();
+        let range = (1., 0.5, 5.);
+        let dvb = Puncturer::dvb("3/4");
+        assert!(check_settings(&k3, "soft", Some(&dvb), range, 64, 0.95).is_ok());
+        assert!(check_settings(&k3, "hard", Some(&dvb), range, 64, 0.95).is_err());
+        assert!(check_settings(&ConvolutionalCode::lte(), "soft", Some(&dvb), range, 64, 0.95).is_err());
+        assert!(check_settings(&k3, "soft", None, range, 64, 2.).is_err());
+        assert!(check_settings(&k3, "soft", None, range, 2, 0.95).is_err());
+        assert!(check_settings(&k3, "soft", None, (1., 0., 5.), 64, 0.95).is_err());
+        assert!(check_settings(&k3, "soft", None, (5., 0.5, 1.), 64, 0.95).is_err());
+        assert!(check_settings(&k3, "viterbi", None, range, 64, 0.95).is_err());
+    }
+
+    #[test]
+    fn snr_grid_includes_end_point() {
+        let vs = test_simu("soft", ConvolutionalCode::k3(), (0., 0.1, 0.3), 64, 10);
+        assert_eq!(vs.len, 4);
+        let vs = test_simu("soft", ConvolutionalCode::k3(), (1., 0.5, 5.), 64, 10);
+        assert_eq!(vs.len, 9);
+    }
+
+    #[test]
+    fn ebn0_counts_termination_tail() {
+        // nasa の 64 シンボルのフレームは 0 で終端すると情報ビットが 58、送るビットが 128
+        let mut vs = test_simu("soft", ConvolutionalCode::nasa(), (0., 1., 0.), 64, 10)
+            .with_unit(SnrUnit::EsN0);
+        assert_eq!(vs.frame_rate(), 58. / 128.);
+        vs.bit_per_error();
+        assert!((vs.points[0].ebn0 - 10. * (128_f64 / 58.).log10()).abs() < 1e-12);
+        // 終端しない復号器は rate のまま
+        let stream = test_simu("stream", ConvolutionalCode::nasa(), (0., 1., 0.), 64, 10);
+        assert_eq!(stream.frame_rate(), 0.5);
+    }
+
+    fn run(threads: usize) -> ViterbiSimu {
+        let mut vs = test_simu("soft", ConvolutionalCode::k3(), (1., 1., 4.), 64, 2000)
+            .with_seed(7)
+            .with_max_errors(150)
+            .with_threads(threads);
+        vs.simu();
+        vs
+    }
+
+    #[test]
+    fn same_result_for_any_thread_count() {
+        let one = run(1);
+        let four = run(4);
+        assert_eq!(one.stats, four.stats);
+        assert_eq!(one.ngs, four.ngs);
+        // 1 dB は max_errors で打ち切られ、4 dB は最後まで送る
+        assert!(one.stats[0].frames < 2000);
+        assert_eq!(one.stats[3].frames, 2000);
+    }
+}
@@ -0,0 +1,142 @@
+// SN 比の単位と、そこから通信路の雑音の標準偏差 sigma を出す計算
+// 1次元あたりの振幅を 1 とし、雑音は1次元あたり分散 sigma^2 = N0 / 2
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SnrUnit {
+    // 情報1ビットあたりのエネルギー / 雑音電力密度
+    EbN0,
+    // 送信1シンボルあたりのエネルギー / 雑音電力密度
+    EsN0,
+    // 1次元あたりの信号電力 / 雑音電力 (1 / sigma^2)
+    Snr,
+}
+
+impl SnrUnit {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SnrUnit::EbN0 => "Eb/N0 [dB]",
+            SnrUnit::EsN0 => "Es/N0 [dB]",
+            SnrUnit::Snr => "SNR [dB]",
+        }
+    }
+}
+
+impl FromStr for SnrUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ebn0" => Ok(SnrUnit::EbN0),
+            "esn0" => Ok(SnrUnit::EsN0),
+            "snr" => Ok(SnrUnit::Snr),
+            _ => Err(format!("unknown SNR unit {} (ebn0, esn0, snr)", s)),
+        }
+    }
+}
+
+// FromStr で読める名前
+impl fmt::Display for SnrUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SnrUnit::EbN0 => "ebn0",
+            SnrUnit::EsN0 => "esn0",
+            SnrUnit::Snr => "snr",
+        })
+    }
+}
+
+// 変調方式 (どちらも1次元ずつ BPSK の通信路で送る)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Modulation {
+    Bpsk,
+    // グレイ符号の QPSK (I と Q がそれぞれ BPSK)
+    Qpsk,
+}
+
+impl Modulation {
+    // 1シンボルで送る符号化後のビット数 log2(M)
+    pub fn bits_per_symbol(&self) -> usize {
+        match self {
+            Modulation::Bpsk => 1,
+            Modulation::Qpsk => 2,
+        }
+    }
+
+    // 1シンボルの次元数 (= 1シンボルのエネルギー)
+    pub fn dimensions(&self) -> usize {
+        match self {
+            Modulation::Bpsk => 1,
+            Modulation::Qpsk => 2,
+        }
+    }
+}
+
+impl FromStr for Modulation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bpsk" => Ok(Modulation::Bpsk),
+            "qpsk" => Ok(Modulation::Qpsk),
+            _ => Err(format!("unknown modulation {} (bpsk, qpsk)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Modulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Modulation::Bpsk => "bpsk",
+            Modulation::Qpsk => "qpsk",
+        })
+    }
+}
+
+fn to_db(x: f64) -> f64 {
+    10. * x.log10()
+}
+
+// 符号化率 rate (パンクチャ後) で変調 modulation のとき、unit で db の SN を Es/N0 [dB] に直す
+pub fn es_n0(db: f64, unit: SnrUnit, rate: f64, modulation: Modulation) -> f64 {
+    match unit {
+        SnrUnit::EbN0 => db + to_db(rate * modulation.bits_per_symbol() as f64),
+        SnrUnit::EsN0 => db,
+        // Es/N0 = dimensions / (2 sigma^2) = dimensions * SNR / 2
+        SnrUnit::Snr => db + to_db(modulation.dimensions() as f64 / 2.),
+    }
+}
+
+// Es/N0 [dB] を Eb/N0 [dB] に直す
+pub fn eb_n0(es_n0: f64, rate: f64, modulation: Modulation) -> f64 {
+    es_n0 - to_db(rate * modulation.bits_per_symbol() as f64)
+}
+
+// 1次元あたりの雑音の標準偏差
+pub fn sigma(db: f64, unit: SnrUnit, rate: f64, modulation: Modulation) -> f64 {
+    let es_n0 = 10_f64.powf(es_n0(db, unit, rate, modulation) / 10.);
+    (modulation.dimensions() as f64 / (2. * es_n0)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigma_is_one_at_0_db_for_rate_half_bpsk() {
+        assert!((sigma(0., SnrUnit::EbN0, 0.5, Modulation::Bpsk) - 1.).abs() < 1e-12);
+        // SNR = 1 / sigma^2
+        assert!((sigma(0., SnrUnit::Snr, 0.5, Modulation::Bpsk) - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bpsk_and_qpsk_have_same_sigma_at_same_ebn0() {
+        for db in [-2., 0., 3.5, 8.] {
+            let bpsk = sigma(db, SnrUnit::EbN0, 0.5, Modulation::Bpsk);
+            let qpsk = sigma(db, SnrUnit::EbN0, 0.5, Modulation::Qpsk);
+            assert!((bpsk - qpsk).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use rand::Rng;
+
+use crate::rng::SeededRng;
+
+// 送りたい情報ビットを作る
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub rng: SeededRng,
+}
+
+impl Source {
+    pub fn new(seed: u64) -> Self {
+        Source { rng: SeededRng::new(seed) }
+    }
+
+    pub fn bits(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.rng.gen::<bool>() as u8).collect()
+    }
+}
@@ -0,0 +1,155 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::trellis::Trellis;
+
+// 誤りイベント (状態0から出て初めて状態0に戻る経路) の出力重みと入力重み
+fn weights(trellis: &Trellis, branch: usize) -> (usize, usize) {
+    let branch = &trellis.branches[branch];
+    (branch.output.count_ones() as usize, branch.input.count_ones() as usize)
+}
+
+// 自由距離 (誤りイベントの出力重みの最小値)
+pub fn free_distance(trellis: &Trellis) -> usize {
+    let mut best = usize::MAX;
+    let mut distance = vec![usize::MAX; trellis.num_states];
+    let mut queue = BinaryHeap::new();
+    for &b in trellis.next[0].iter().skip(1) {
+        let to = trellis.branches[b].to;
+        let (w, _) = weights(trellis, b);
+        if to == 0 {
+            best = best.min(w);
+        } else if w < distance[to] {
+            distance[to] = w;
+            queue.push(Reverse((w, to)));
+        }
+    }
+    // ダイクストラ法
+    while let Some(Reverse((d, state))) = queue.pop() {
+        if d > distance[state] || d >= best {
+            continue;
+        }
+        for &b in &trellis.next[state] {
+            let to = trellis.branches[b].to;
+            let w = d + weights(trellis, b).0;
+            if to == 0 {
+                best = best.min(w);
+            } else if w < distance[to] {
+                distance[to] = w;
+                queue.push(Reverse((w, to)));
+            }
+        }
+    }
+    best
+}
+
+// 距離スペクトル
+// paths[i] は出力重み free_distance + i の誤りイベントの数 A_d、
+// weights[i] はそれらの入力重みの和 B_d
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceSpectrum {
+    pub free_distance: usize,
+    pub paths: Vec<usize>,
+    pub weights: Vec<usize>,
+}
+
+impl DistanceSpectrum {
+    // 自由距離から terms 個の距離について数える
+    pub fn new(trellis: &Trellis, terms: usize) -> Self {
+        if let Some(states) = trellis.zero_weight_loop() {
+            panic!("code is catastrophic (zero weight loop through states {:?}), its distance spectrum is infinite", states);
+        }
+        let free_distance = free_distance(trellis);
+        let max_weight = free_distance + terms - 1;
+        let mut paths = vec![0; terms];
+        let mut info_weights = vec![0; terms];
+        let mut add = |w: usize, count: usize, info: usize| {
+            if w >= free_distance && w <= max_weight {
+                paths[w - free_distance] += count;
+                info_weights[w - free_distance] += info;
+            }
+        };
+
+        // alive[state][w] = (状態0に戻っていない出力重み w の経路の数, それらの入力重みの和)
+        let mut alive = vec![vec![(0, 0); max_weight + 1]; trellis.num_states];
+        for &b in trellis.next[0].iter().skip(1) {
+            let to = trellis.branches[b].to;
+            let (w, i) = weights(trellis, b);
+            if w > max_weight {
+                continue;
+            }
+            if to == 0 {
+                add(w, 1, i);
+            } else {
+                alive[to][w].0 += 1;
+                alive[to][w].1 += i;
+            }
+        }
+        // 出力0のループがないので、いつか全部の経路が max_weight を超える
+        while alive.iter().flatten().any(|(count, _)| *count > 0) {
+            let mut next = vec![vec![(0, 0); max_weight + 1]; trellis.num_states];
+            for (state, by_weight) in alive.iter().enumerate() {
+                for (w, &(count, info)) in by_weight.iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    for &b in &trellis.next[state] {
+                        let to = trellis.branches[b].to;
+                        let (bw, bi) = weights(trellis, b);
+                        let w = w + bw;
+                        if w > max_weight {
+                            continue;
+                        }
+                        let info = info + count * bi;
+                        if to == 0 {
+                            add(w, count, info);
+                        } else {
+                            next[to][w].0 += count;
+                            next[to][w].1 += info;
+                        }
+                    }
+                }
+            }
+            alive = next;
+        }
+
+        DistanceSpectrum {
+            free_distance,
+            paths,
+            weights: info_weights,
+        }
+    }
+
+    // (d, A_d)
+    pub fn path_counts(&self) -> Vec<(usize, usize)> {
+        (self.free_distance..).zip(self.paths.iter().copied()).collect()
+    }
+
+    // (d, B_d) (theory::union_bound に渡す形)
+    pub fn information_weights(&self) -> Vec<(usize, usize)> {
+        (self.free_distance..).zip(self.weights.iter().copied()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::ConvolutionalCode;
+
+    // 教科書の表の値 (Proakis など)
+    #[test]
+    fn spectrum_of_k3() {
+        let spectrum = DistanceSpectrum::new(&Trellis::new(&ConvolutionalCode::k3()), 4);
+        assert_eq!(spectrum.free_distance, 5);
+        assert_eq!(spectrum.paths, vec![1, 2, 4, 8]);
+        assert_eq!(spectrum.weights, vec![1, 4, 12, 32]);
+    }
+
+    #[test]
+    fn spectrum_of_nasa() {
+        let spectrum = DistanceSpectrum::new(&Trellis::new(&ConvolutionalCode::nasa()), 5);
+        assert_eq!(spectrum.free_distance, 10);
+        assert_eq!(spectrum.paths, vec![11, 0, 38, 0, 193]);
+        assert_eq!(spectrum.weights, vec![36, 0, 211, 0, 1404]);
+    }
+}
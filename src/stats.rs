@@ -0,0 +1,215 @@
+// BER の信頼区間と、そのための特殊関数
+
+// 相補誤差関数 (Numerical Recipes の erfcc、相対誤差 1.2e-7 以下)
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+        + t * (0.374_091_96
+        + t * (0.096_784_18
+        + t * (-0.186_288_06
+        + t * (0.278_868_07
+        + t * (-1.135_203_98
+        + t * (1.488_515_87
+        + t * (-0.822_152_23
+        + t * 0.170_872_77))))))));
+    let r = t * poly.exp();
+    if x >= 0. { r } else { 2. - r }
+}
+
+// 標準正規分布の上側確率 Q(x) = P(X > x)
+pub fn q(x: f64) -> f64 {
+    0.5 * erfc(x / 2_f64.sqrt())
+}
+
+// 信頼度 confidence の両側区間の幅 z (Q(z) = (1 - confidence) / 2)
+pub fn z_score(confidence: f64) -> f64 {
+    let target = (1. - confidence) / 2.;
+    let (mut lo, mut hi) = (0., 40.);
+    for _ in 0..100 {
+        let mid = (lo + hi) / 2.;
+        if q(mid) > target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+// log Γ(x) (Lanczos 近似)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series: f64 = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c / (x + 1. + i as f64))
+        .sum();
+    -tmp + (2.506_628_274_631_000_5 * (1.000_000_000_190_015 + series) / x).ln()
+}
+
+// 正則化不完全ベータ関数の連分数部分
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let (qab, qap, qam) = (a + b, a + 1., a - 1.);
+    let mut c = 1.;
+    let mut d = 1. - qab * x / qap;
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1. / d;
+    let mut h = d;
+    for m in 1..100_000 {
+        let m = m as f64;
+        let m2 = 2. * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1. + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1. + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1. / d;
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1. + aa * d;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = 1. + aa / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1. / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.).abs() < 1e-12 {
+            break;
+        }
+    }
+    h
+}
+
+// 正則化不完全ベータ関数 I_x(a, b) (ベータ分布の累積分布関数)
+pub fn beta_cdf(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1. - front * beta_fraction(b, a, 1. - x) / b
+    }
+}
+
+// I_x(a, b) = p となる x (二分法)
+pub fn beta_quantile(a: f64, b: f64, p: f64) -> f64 {
+    let (mut lo, mut hi) = (0., 1.);
+    for _ in 0..200 {
+        let mid = (lo + hi) / 2.;
+        if beta_cdf(a, b, mid) < p {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.
+}
+
+// n 回中 k 回誤ったときの誤り率の Wilson スコア区間
+pub fn wilson(errors: usize, trials: usize, confidence: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0., 1.);
+    }
+    let n = trials as f64;
+    let p = errors as f64 / n;
+    let z = z_score(confidence);
+    let z2 = z * z;
+    let denominator = 1. + z2 / n;
+    let center = (p + z2 / (2. * n)) / denominator;
+    let half = z / denominator * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt();
+    ((center - half).max(0.), (center + half).min(1.))
+}
+
+// n 回中 k 回誤ったときの誤り率の Clopper-Pearson (正確な二項) 区間
+pub fn clopper_pearson(errors: usize, trials: usize, confidence: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0., 1.);
+    }
+    let alpha = 1. - confidence;
+    let (k, n) = (errors as f64, trials as f64);
+    let lower = if errors == 0 { 0. } else { beta_quantile(k, n - k + 1., alpha / 2.) };
+    let upper = if errors == trials { 1. } else { beta_quantile(k + 1., n - k, 1. - alpha / 2.) };
+    (lower, upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64, tolerance: f64) -> bool {
+        (actual - expected).abs() <= tolerance
+    }
+
+    #[test]
+    fn erfc_known_values() {
+        for (x, expected) in [(0., 1.), (1., 0.157_299_207), (-1., 1.842_700_793), (2., 0.004_677_735)] {
+            assert!(close(erfc(x), expected, 1.2e-7 * expected), "erfc({}) = {}", x, erfc(x));
+        }
+        assert!(close(q(0.), 0.5, 1e-7));
+    }
+
+    #[test]
+    fn z_score_known_values() {
+        assert!(close(z_score(0.95), 1.959_964, 1e-5));
+        assert!(close(z_score(0.99), 2.575_829, 1e-5));
+    }
+
+    #[test]
+    fn beta_quantile_inverts_simple_distributions() {
+        for p in [0.1, 0.5, 0.9] {
+            // Beta(1, 1) は一様分布、Beta(2, 1) の分布関数は x^2
+            assert!(close(beta_quantile(1., 1., p), p, 1e-9));
+            assert!(close(beta_quantile(2., 1., p), p.sqrt(), 1e-9));
+        }
+    }
+
+    #[test]
+    fn clopper_pearson_known_values() {
+        let (low, high) = clopper_pearson(10, 1000, 0.95);
+        assert!(close(low, 0.004_806, 1e-6) && close(high, 0.018_313, 1e-6), "{} {}", low, high);
+        // 誤りなしなら上限は 1 - (alpha / 2)^(1/n)、全部誤りならその裏返し
+        assert_eq!(clopper_pearson(0, 100, 0.95).0, 0.);
+        assert!(close(clopper_pearson(0, 100, 0.95).1, 0.036_217, 1e-6));
+        assert!(close(clopper_pearson(100, 100, 0.95).0, 0.963_783, 1e-6));
+        assert_eq!(clopper_pearson(100, 100, 0.95).1, 1.);
+        assert_eq!(clopper_pearson(0, 0, 0.95), (0., 1.));
+    }
+
+    #[test]
+    fn wilson_known_values() {
+        let (low, high) = wilson(10, 1000, 0.95);
+        assert!(close(low, 0.005_441, 1e-6) && close(high, 0.018_309, 1e-6), "{} {}", low, high);
+        let (low, high) = wilson(0, 100, 0.95);
+        assert!(close(low, 0., 1e-12) && close(high, 0.036_993, 1e-6));
+        let (low, high) = wilson(100, 100, 0.95);
+        assert!(close(low, 0.963_007, 1e-6) && close(high, 1., 1e-12));
+        assert_eq!(wilson(0, 0, 0.95), (0., 1.));
+    }
+}
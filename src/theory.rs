@@ -0,0 +1,45 @@
+// 理論値の BER (シミュレーション結果と比べる用)
+use crate::stats::q;
+
+fn from_db(db: f64) -> f64 {
+    10_f64.powf(db / 10.)
+}
+
+// 符号化しない BPSK の BER Q(sqrt(2 Eb/N0))
+pub fn uncoded_bpsk(ebn0_db: f64) -> f64 {
+    q((2. * from_db(ebn0_db)).sqrt())
+}
+
+// 軟判定ビタビ復号の BER の和集合上界
+// sum_d B_d Q(sqrt(2 d R Eb/N0)) / k
+// weights は (距離 d, その距離の経路の情報ビットの重みの和 B_d)、k は1時刻の入力ビット数
+pub fn union_bound(ebn0_db: f64, rate: f64, k: usize, weights: &[(usize, usize)]) -> f64 {
+    let ebn0 = from_db(ebn0_db);
+    let bound: f64 = weights.iter()
+                            .map(|(d, b)| *b as f64 * q((2. * *d as f64 * rate * ebn0).sqrt()))
+                            .sum();
+    // SN が低いと上界が意味のない大きさになるので 0.5 で切る
+    (bound / k as f64).min(0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncoded_bpsk_reaches_1e_5_at_9_6_db() {
+        let ber = uncoded_bpsk(9.6);
+        assert!((ber - 9.736e-6).abs() < 1e-9, "{}", ber);
+        assert_eq!(uncoded_bpsk(0.), q(2_f64.sqrt()));
+    }
+
+    #[test]
+    fn union_bound_of_k3() {
+        // K=3 (7, 5) の B_d = (d - 4) 2^(d - 5)
+        let weights = [(5, 1), (6, 4), (7, 12), (8, 32)];
+        let bound = union_bound(5., 0.5, 1, &weights);
+        assert!((bound - 8.4596e-5).abs() < 1e-8, "{}", bound);
+        // 低い SN では 0.5 で切る
+        assert_eq!(union_bound(-5., 0.5, 1, &weights), 0.5);
+    }
+}
@@ -1,350 +1,120 @@
-use crate::box_muller::box_muller;
-use std::ops::{Add, Sub};
-use std::usize;
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum SMState {
-    OO,
-    OI,
-    IO,
-    II,
-}
-
-impl Into<SMState> for usize {
-    fn into(self) -> SMState {
-        match self {
-            0 => SMState::OO,
-            1 => SMState::OI,
-            2 => SMState::IO,
-            3 => SMState::II,
-            _ => panic!("cant convert from int to SMState"),
-        }
-    }
-}
-
-impl Into<usize> for SMState {
-    fn into(self) -> usize {
-        match self {
-            SMState::OO => 0,
-            SMState::OI => 1,
-            SMState::IO => 2,
-            SMState::II => 3,
-        }
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum Signal {
-    OO,
-    OI,
-    IO,
-    II,
+use crate::binary;
+use crate::code::ConvolutionalCode;
+use crate::metric::Metric;
+
+// 状態 from で input を入れると状態 to に遷移して output を出す枝
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Branch {
+    pub from: usize,
+    pub to: usize,
+    pub input: usize,
+    pub output: usize,
+}
+
+// 符号から作ったトレリス
+// 全ての復号器はこれをたどって状態遷移を調べる
+#[derive(Debug, Clone)]
+pub struct Trellis {
+    pub num_states: usize,
+    pub num_inputs: usize,
+    // 1つの枝の入力ビット数・出力ビット数
+    pub k: usize,
+    pub n: usize,
+    pub branches: Vec<Branch>,
+    // [state][input] -> branches の添字
+    pub next: Vec<Vec<usize>>,
+    // [state] -> その状態に入ってくる枝の branches の添字
+    pub predecessors: Vec<Vec<usize>>,
 }
 
-impl Signal {
-    pub fn add_noise(&self, sigma: f64) -> Signal {
-        let first;
-        let second;
-        match *self {
-            Signal::OO => {
-                first = -1.0 + sigma * box_muller();
-                second = -1.0 + sigma * box_muller();
-            }
-            Signal::OI => {
-                first = -1.0 + sigma * box_muller();
-                second = 1.0 + sigma * box_muller();
-            }
-            Signal::IO => {
-                first = 1.0 + sigma * box_muller();
-                second = -1.0 + sigma * box_muller();
-            }
-            Signal::II => {
-                first = 1.0 + sigma * box_muller();
-                second = 1.0 + sigma * box_muller();
+impl Trellis {
+    pub fn new(code: &ConvolutionalCode) -> Self {
+        let num_states = code.num_states;
+        let num_inputs = 1 << code.k;
+        let mut branches = Vec::with_capacity(num_states * num_inputs);
+        let mut next = vec![Vec::with_capacity(num_inputs); num_states];
+        let mut predecessors = vec![Vec::new(); num_states];
+        for (from, next_branches) in next.iter_mut().enumerate() {
+            for input in 0..num_inputs {
+                let branch = Branch {
+                    from,
+                    to: code.next_state[from][input],
+                    input,
+                    output: code.output[from][input],
+                };
+                next_branches.push(branches.len());
+                predecessors[branch.to].push(branches.len());
+                branches.push(branch);
             }
         }
-        // dbg!(first, second);
-        match (first > 0.0, second > 0.0) {
-            (false, false) => Signal::OO,
-            (false, true) => Signal::OI,
-            (true, false) => Signal::IO,
-            (true, true) => Signal::II,
-        }
-    }
-}
-
-impl Into<Signal> for usize {
-    fn into(self) -> Signal {
-        match self {
-            0 => Signal::OO,
-            1 => Signal::OI,
-            2 => Signal::IO,
-            3 => Signal::II,
-            _ => panic!("cant convert from int to SMState"),
+        Trellis {
+            num_states,
+            num_inputs,
+            k: code.k,
+            n: code.n,
+            branches,
+            next,
+            predecessors,
         }
     }
-}
-
-impl Into<usize> for Signal {
-    fn into(self) -> usize {
-        match self {
-            Signal::OO => 0,
-            Signal::OI => 1,
-            Signal::IO => 2,
-            Signal::II => 3,
-        }
-    }
-}
-
-impl Sub for Signal {
-    type Output = usize;
-    fn sub(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Signal::OO, Signal::OO) => 0,
-            (Signal::OO, Signal::OI) => 1,
-            (Signal::OO, Signal::IO) => 1,
-            (Signal::OO, Signal::II) => 2,
-
-            (Signal::OI, Signal::OO) => 1,
-            (Signal::OI, Signal::OI) => 0,
-            (Signal::OI, Signal::IO) => 2,
-            (Signal::OI, Signal::II) => 1,
-
-            (Signal::IO, Signal::OO) => 1,
-            (Signal::IO, Signal::OI) => 2,
-            (Signal::IO, Signal::IO) => 0,
-            (Signal::IO, Signal::II) => 1,
-
-            (Signal::II, Signal::OO) => 2,
-            (Signal::II, Signal::OI) => 1,
-            (Signal::II, Signal::IO) => 1,
-            (Signal::II, Signal::II) => 0,
-        }
-    }
-}
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum Bit {
-    O,
-    I,
-}
 
-impl Into<Bit> for usize {
-    fn into(self) -> Bit {
-        if self == 0 {
-            Bit::O
-        } else if self == 1 {
-            Bit::I
-        } else {
-            panic!("cant convert from into to Bit")
-        }
+    pub fn branch(&self, state: usize, input: usize) -> &Branch {
+        &self.branches[self.next[state][input]]
     }
-}
 
-impl Into<usize> for Bit {
-    fn into(self) -> usize {
-        match self {
-            Bit::O => 0,
-            Bit::I => 1,
-        }
+    // 受信シンボルと枝の出力の距離
+    pub fn distance<T: Metric>(&self, branch: &Branch, received: &[T]) -> f64 {
+        received.iter()
+                .enumerate()
+                .map(|(j, r)| r.distance(binary::bit(branch.output, self.n, j)))
+                .sum()
     }
-}
 
-impl Add for Bit {
-    type Output = usize;
-    fn add(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Bit::O, Bit::O) => 0,
-            (Bit::O, Bit::I) | (Bit::I, Bit::O) => 1,
-            (Bit::I, Bit::I) => 2,
-        }
+    pub fn incoming(&self, state: usize) -> impl Iterator<Item = &Branch> {
+        self.predecessors[state].iter().map(move |b| &self.branches[*b])
     }
-}
-
-#[derive(Debug, Copy, Clone)]
-pub struct StateMachine {
-    pub state: SMState,
-}
 
-impl StateMachine {
-    pub fn new(sms: SMState) -> Self {
-        StateMachine { state: sms }
-    }
-    pub fn set(&mut self, bit: Bit) -> Signal {
-        match (bit, self.state) {
-            (Bit::O, SMState::OO) => {
-                self.state = SMState::OO;
-                Signal::OO
+    // 出力が全部0のまま回り続けるループ (状態0で0を入れ続けるものは除く) の状態の列
+    // これがあると、有限個の誤りで無限個の情報ビットが誤る (破滅的符号)
+    pub fn zero_weight_loop(&self) -> Option<Vec<usize>> {
+        let zero_weight: Vec<Vec<usize>> = (0..self.num_states)
+            .map(|state| {
+                self.next[state]
+                    .iter()
+                    .map(|b| &self.branches[*b])
+                    .filter(|branch| branch.output == 0 && !(branch.from == 0 && branch.input == 0))
+                    .map(|branch| branch.to)
+                    .collect()
+            })
+            .collect();
+        // 深さ優先探索で戻る辺を探す (0: 未訪問, 1: 探索中, 2: 済み)
+        let mut color = vec![0u8; self.num_states];
+        for root in 0..self.num_states {
+            if color[root] != 0 {
+                continue;
             }
-            (Bit::I, SMState::OO) => {
-                self.state = SMState::IO;
-                Signal::II
-            }
-            (Bit::O, SMState::OI) => {
-                self.state = SMState::OO;
-                Signal::II
-            }
-            (Bit::I, SMState::OI) => {
-                self.state = SMState::IO;
-                Signal::OO
-            }
-            (Bit::O, SMState::IO) => {
-                self.state = SMState::OI;
-                Signal::IO
-            }
-            (Bit::I, SMState::IO) => {
-                self.state = SMState::II;
-                Signal::OI
-            }
-            (Bit::O, SMState::II) => {
-                self.state = SMState::OI;
-                Signal::OI
-            }
-            (Bit::I, SMState::II) => {
-                self.state = SMState::II;
-                Signal::IO
-            }
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct Trellis {
-    pub table: [[[[Option<Bit>; 4]; 4]; 4]; 4],
-}
-
-impl Trellis {
-    pub fn new() -> Self {
-        let mut table = [[[[None; 4]; 4]; 4]; 4];
-        for sms in 0..4 {
-            for first_signal in 0..4 {
-                for second_signal in 0..4 {
-                    for third_signal in 0..4 {
-                        let mut OO_bits = None;
-                        let mut OI_bits = None;
-                        let mut IO_bits = None;
-                        let mut II_bits = None;
-                        let mut OO_min_diff = None;
-                        let mut OI_min_diff = None;
-                        let mut IO_min_diff = None;
-                        let mut II_min_diff = None;
-
-                        for first_bit in 0..2 {
-                            for second_bit in 0..2 {
-                                for third_bit in 0..2 {
-                                    // calculate
-                                    let mut sm: StateMachine = StateMachine::new(sms.into());
-
-                                    let fs: Signal = first_signal.into();
-                                    let ss: Signal = second_signal.into();
-                                    let ts: Signal = third_signal.into();
-
-                                    let mut diff = Some(0);
-                                    diff = diff.map(|a| a + (fs - sm.set(first_bit.into())));
-                                    diff = diff.map(|a| a + (ss - sm.set(second_bit.into())));
-                                    diff = diff.map(|a| a + (ts - sm.set(third_bit.into())));
-
-                                    match sm.state {
-                                        SMState::OO => {
-                                            if OO_min_diff > diff || OO_min_diff == None {
-                                                OO_min_diff = diff;
-                                                OO_bits = Some([first_bit, second_bit, third_bit]);
-                                            } else if OO_min_diff == diff {
-                                                OO_min_diff = None;
-                                                OO_bits = None;
-                                                panic!("wow equal diff in OO");
-                                            }
-                                        }
-                                        SMState::OI => {
-                                            if OI_min_diff > diff || OI_min_diff == None {
-                                                OI_min_diff = diff;
-                                                OI_bits = Some([first_bit, second_bit, third_bit]);
-                                            } else if OI_min_diff == diff {
-                                                OI_min_diff = None;
-                                                OI_bits = None;
-                                                panic!("wow equal diff in OI");
-                                            }
-                                        }
-                                        SMState::IO => {
-                                            if IO_min_diff > diff || IO_min_diff == None {
-                                                IO_min_diff = diff;
-                                                IO_bits = Some([first_bit, second_bit, third_bit]);
-                                            } else if IO_min_diff == diff {
-                                                IO_min_diff = None;
-                                                IO_bits = None;
-                                                panic!("wow equal diff in IO");
-                                            }
-                                        }
-                                        SMState::II => {
-                                            if II_min_diff > diff || II_min_diff == None {
-                                                II_min_diff = diff;
-                                                II_bits = Some([first_bit, second_bit, third_bit]);
-                                            } else if II_min_diff == diff {
-                                                II_min_diff = None;
-                                                II_bits = None;
-                                                panic!("wow equal diff in II");
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        let bits_sum = vec![OO_bits, OI_bits, IO_bits, II_bits]
-                            .into_iter()
-                            .filter(|a| *a != None)
-                            .map(|a| a.unwrap()[0])
-                            .fold(0, |acc, item| if item > 1 {panic!("wow")} else {acc + Into::<usize>::into(item)});
-                        // dbg!(bits_sum);
-                        if bits_sum < 2 {
-                            table[sms][first_signal][second_signal][third_signal] = Some(Bit::O);
-                        } else if bits_sum > 2 {
-                            table[sms][first_signal][second_signal][third_signal] = Some(Bit::I);
-                        } else {
-                            panic!("wow not much");
-                            table[sms][first_signal][second_signal][third_signal] = None;
+            // (状態, 次に見る枝の番号)
+            let mut stack = vec![(root, 0)];
+            color[root] = 1;
+            while let Some((state, i)) = stack.pop() {
+                if let Some(&to) = zero_weight[state].get(i) {
+                    stack.push((state, i + 1));
+                    match color[to] {
+                        0 => {
+                            color[to] = 1;
+                            stack.push((to, 0));
                         }
-
-                        /*
-                        let mut diffs = vec![
-                            (Signal::OO, OO_min_diff),
-                            (Signal::OI, OI_min_diff),
-                            (Signal::IO, IO_min_diff),
-                            (Signal::II, II_min_diff),
-                        ];
-                        diffs.sort_by_key(|e| e.1);
-                        if diffs[0].1 == diffs[1].1 {
-                            if rand::random::<bool>() {
-                                // dbg!("swap");
-                                diffs.swap(0, 1);
-                            }
-                        }
-                        match diffs[0].0 {
-                            Signal::OO => {
-                                table[sms][first_signal][second_signal][third_signal] = Some(OO_bits.unwrap()[0].into());
-                            }
-                            Signal::OI => {
-                                table[sms][first_signal][second_signal][third_signal] = Some(OI_bits.unwrap()[0].into());
-                            }
-                            Signal::IO => {
-                                table[sms][first_signal][second_signal][third_signal] = Some(IO_bits.unwrap()[0].into());
-                            }
-                            Signal::II => {
-                                table[sms][first_signal][second_signal][third_signal] = Some(II_bits.unwrap()[0].into());
-                            }
+                        1 => {
+                            let start = stack.iter().position(|(s, _)| *s == to).unwrap();
+                            return Some(stack[start..].iter().map(|(s, _)| *s).collect());
                         }
-                        */
+                        _ => {}
                     }
+                } else {
+                    color[state] = 2;
                 }
             }
         }
-        // dbg!(table);
-        return Trellis { table };
-    }
-
-    pub fn get_next_bit(&self, sms: SMState, sss: (Signal, Signal, Signal)) -> Bit {
-        self.table[Into::<usize>::into(sms)][Into::<usize>::into(sss.0)][Into::<usize>::into(sss.1)]
-            [Into::<usize>::into(sss.2)]
-            .unwrap()
+        None
     }
 }
@@ -1,105 +1,188 @@
-use crate::trellis;
+use std::marker::PhantomData;
 
-mod hard;
-mod hard_dp;
-mod soft;
+use crate::encoder::Termination;
+use crate::metric::{Metric, Quantized};
+use crate::trellis::Trellis;
 
-pub trait Viterbi {
-    fn new(len: usize, sigma: f64) -> Self;
-    fn get_raw_request_data(&self) -> &Vec<trellis::Bit>;
-    fn get_raw_answer_data(&self) -> &Vec<trellis::Bit>;
-    fn decode(&mut self, trellis: &trellis::Trellis);
+pub mod bcjr;
+pub mod greedy;
+pub mod hard_dp;
+pub mod soft;
+pub mod sova;
+pub mod stream;
+pub mod tail_biting;
+
+pub use bcjr::{Bcjr, MaxStar};
+pub use greedy::GreedySearch;
+pub use hard_dp::ViterbiHardDP;
+pub use soft::ViterbiSoft;
+pub use sova::ViterbiSova;
+pub use stream::ViterbiStream;
+pub use tail_biting::ViterbiTailBiting;
+
+// 受信シンボルの型が T の復号器
+// received は trellis.n 個ずつのシンボルに区切って読み、最後の1シンボルに満たない半端な分は無視する
+pub trait Decoder<T: Metric> {
+    fn decode(&mut self, received: &[T]) -> Vec<u8>;
 }
 
+// 通信路から来た受信信号 (bpsk) を復号器の入力の型に直して復号する
+pub trait ChannelDecoder {
+    fn decode_samples(&mut self, received: &[f64]) -> Vec<u8>;
+}
 
-#[derive(Debug)]
-pub struct ViterbiSimu {
-    pub way: String,
-    pub len: usize,
-    pub start_db: f64,
-    pub tick_db: f64,
-    pub end_db: f64,
-    pub bits_len: usize,
-    pub iteration: usize,
-    pub ber: Vec<(f64, f64)>,
-    pub oks: Vec<usize>,
-    pub ngs: Vec<usize>,
+struct WithMetric<T, D> {
+    decoder: D,
+    metric: PhantomData<T>,
 }
 
-impl ViterbiSimu {
-    pub fn new(way: String,
-               start_db: f64,
-               tick_db: f64,
-               end_db: f64,
-               bits_len: usize,
-               iteration: usize,
-    ) -> ViterbiSimu {
-        let len = ((end_db - start_db) / tick_db) as usize + 1;
-        let ber = (0..len)
-            .map(|a| start_db + a as f64 * tick_db)
-            .map(|a| (a, 0.)).collect();
-        return ViterbiSimu {
-            way,
-            len,
-            start_db,
-            tick_db,
-            end_db,
-            bits_len,
-            iteration,
-            ber,
-            oks: vec![0; len],
-            ngs: vec![0; len],
-        };
+impl<T: Metric, D: Decoder<T>> ChannelDecoder for WithMetric<T, D> {
+    fn decode_samples(&mut self, received: &[f64]) -> Vec<u8> {
+        let received: Vec<T> = received.iter().map(|r| T::from_sample(*r)).collect();
+        self.decoder.decode(&received)
     }
+}
 
-    pub fn simu(&mut self) {
-        let trellis = trellis::Trellis::new();
-        let lines: Vec<f64> = (0..self.len as usize)
-            .map(|a| self.start_db + a as f64 * self.tick_db).collect();
-        for (i, sn) in lines.iter().enumerate() {
-            for _ in 0..self.iteration {
-                let sigma = 1.0 / ((10.0 as f64).powf(sn / 10.0) * 2.0).sqrt();
-                if &self.way == "hard" {
-                    let mut viterbi = hard::ViterbiHard::new(self.bits_len, sigma);
-                    viterbi.decode(&trellis);
-                    for (r, a) in viterbi.get_raw_request_data().iter().zip(viterbi.get_raw_answer_data()) {
-                        if r == a {
-                            self.oks[i] += 1;
-                        } else {
-                            self.ngs[i] += 1;
-                        }
-                    }
-                } else if &self.way == "hard-dp" {
-                    let mut viterbi = hard_dp::ViterbiHardDP::new(self.bits_len, sigma);
-                    viterbi.decode(&trellis);
-                    for (r, a) in viterbi.get_raw_request_data().iter().zip(viterbi.get_raw_answer_data()) {
-                        if r == a {
-                            self.oks[i] += 1;
-                        } else {
-                            self.ngs[i] += 1;
-                        }
-                    }
-                } else if &self.way == "soft" {
-                    let mut viterbi = soft::ViterbiSoft::new(self.bits_len, sigma);
-                    viterbi.decode();
-                    for (r, a) in viterbi.raw_request_data.iter().zip(&viterbi.raw_answer_data) {
-                        if r == a {
-                            self.oks[i] += 1;
-                        } else {
-                            self.ngs[i] += 1;
-                        }
-                    }
-                } else {
-                    panic!("i don't know");
-                }
+fn boxed<T: Metric + 'static, D: Decoder<T> + 'static>(decoder: D) -> Box<dyn ChannelDecoder> {
+    Box::new(WithMetric { decoder, metric: PhantomData })
+}
+
+pub struct DecoderEntry {
+    pub name: &'static str,
+    // 送る側のフレームの終わらせ方
+    pub termination: Termination,
+    // パンクチャの erasure (0) をどちらのビットとも同じ距離として扱えるか
+    pub erasure: bool,
+    // 量子化しない受信値で復号するか (軟判定の和集合上界と比べられる)
+    pub soft_metric: bool,
+    // (トレリス, 通信路の sigma, 同点のときに使う乱数のシード) から復号器を作る
+    pub build: fn(&Trellis, f64, u64) -> Box<dyn ChannelDecoder>,
+}
+
+pub const DECODERS: &[DecoderEntry] = &[
+    DecoderEntry {
+        name: "hard",
+        termination: Termination::Zero,
+        erasure: false,
+        soft_metric: false,
+        build: |trellis, _, _| boxed::<u8, _>(soft::ViterbiSoft::new(trellis.clone())),
+    },
+    DecoderEntry {
+        name: "hard-dp",
+        termination: Termination::Zero,
+        erasure: false,
+        soft_metric: false,
+        build: |trellis, _, seed| boxed::<u8, _>(hard_dp::ViterbiHardDP::new(trellis.clone(), seed)),
+    },
+    // ビタビではなく、window シンボル先までの貪欲な探索 (間違えた状態から戻れない)
+    DecoderEntry {
+        name: "greedy",
+        termination: Termination::Zero,
+        erasure: false,
+        soft_metric: false,
+        build: |trellis, _, _| boxed::<u8, _>(greedy::GreedySearch::new(trellis.clone())),
+    },
+    DecoderEntry {
+        name: "soft",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, _, _| boxed::<f64, _>(soft::ViterbiSoft::new(trellis.clone())),
+    },
+    DecoderEntry {
+        name: "soft-quantized",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: false,
+        build: |trellis, _, _| boxed::<Quantized, _>(soft::ViterbiSoft::new(trellis.clone())),
+    },
+    DecoderEntry {
+        name: "sova",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, sigma, _| boxed::<f64, _>(sova::ViterbiSova::new(trellis.clone(), sigma)),
+    },
+    DecoderEntry {
+        name: "stream",
+        termination: Termination::Truncated,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, _, _| {
+            boxed::<f64, _>(stream::ViterbiStream::new(trellis.clone(), stream::default_depth(trellis)))
+        },
+    },
+    DecoderEntry {
+        name: "tail-biting",
+        termination: Termination::TailBiting,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, _, _| boxed::<f64, _>(tail_biting::ViterbiTailBiting::new(trellis.clone())),
+    },
+    DecoderEntry {
+        name: "log-map",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, sigma, _| boxed(bcjr::Bcjr::new(trellis.clone(), sigma, bcjr::MaxStar::LogMap)),
+    },
+    DecoderEntry {
+        name: "max-log-map",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, sigma, _| boxed(bcjr::Bcjr::new(trellis.clone(), sigma, bcjr::MaxStar::MaxLogMap)),
+    },
+    DecoderEntry {
+        name: "max-log-map-table",
+        termination: Termination::Zero,
+        erasure: true,
+        soft_metric: true,
+        build: |trellis, sigma, _| boxed(bcjr::Bcjr::new(trellis.clone(), sigma, bcjr::MaxStar::table(0.5, 8))),
+    },
+];
+
+pub fn find_decoder(name: &str) -> Option<&'static DecoderEntry> {
+    DECODERS.iter().find(|d| d.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary;
+    use crate::code::ConvolutionalCode;
+    use crate::encoder::Encoder;
+
+    // 雑音がなければどの復号器も情報ビットをそのまま返す
+    #[test]
+    fn every_decoder_round_trips_noiseless_frame() {
+        for code in [ConvolutionalCode::lte(), ConvolutionalCode::rate_2_3()] {
+            let trellis = Trellis::new(&code);
+            for entry in DECODERS {
+                let encoder = Encoder::new(code.clone(), entry.termination);
+                let len = 40;
+                let info: Vec<u8> = (0..encoder.info_len(len)).map(|i| ((i * 7 + i / 3) % 5 % 2) as u8).collect();
+                let received: Vec<f64> = encoder.encode(&info).iter().map(|b| binary::bpsk(*b)).collect();
+                let mut decoder = (entry.build)(&trellis, 0.5, 1);
+                let mut decoded = decoder.decode_samples(&received);
+                decoded.truncate(encoder.info_len(len));
+                assert_eq!(decoded, info, "{} {}", entry.name, code.spec());
             }
         }
     }
 
-    pub fn bit_per_error(&mut self) {
-        for i in 0..self.len {
-            // self.ber[i].1 = self.ngs[i] as f64 / (self.oks[i] + self.ngs[i]) as f64;
-            self.ber[i].1 = (self.ngs[i] as f64 / (self.oks[i] + self.ngs[i]) as f64).log10();
+    #[test]
+    fn every_decoder_ignores_trailing_partial_symbol() {
+        let code = ConvolutionalCode::k3();
+        let trellis = Trellis::new(&code);
+        for entry in DECODERS {
+            let encoder = Encoder::new(code.clone(), entry.termination);
+            let info: Vec<u8> = (0..encoder.info_len(10)).map(|i| (i % 3 == 0) as u8).collect();
+            let mut received: Vec<f64> = encoder.encode(&info).iter().map(|b| binary::bpsk(*b)).collect();
+            let whole = (entry.build)(&trellis, 0.5, 1).decode_samples(&received);
+            received.push(0.5);
+            let partial = (entry.build)(&trellis, 0.5, 1).decode_samples(&received);
+            assert_eq!(partial, whole, "{}", entry.name);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,191 @@
+use self::super::Decoder;
+use crate::binary;
+use crate::trellis::Trellis;
+
+// log(e^a + e^b) の計算方法
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaxStar {
+    // max(a, b) + log(1 + e^-|a-b|)
+    LogMap,
+    // max(a, b)
+    MaxLogMap,
+    // max(a, b) + table[|a-b| / step] (表の外は補正なし)
+    Table { step: f64, table: Vec<f64> },
+}
+
+impl MaxStar {
+    // log(1 + e^-x) を step 刻みで size 個並べた補正表
+    pub fn table(step: f64, size: usize) -> Self {
+        let table = (0..size)
+            .map(|i| (1. + (-(i as f64 + 0.5) * step).exp()).ln())
+            .collect();
+        MaxStar::Table { step, table }
+    }
+
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        if a == f64::NEG_INFINITY {
+            return b;
+        }
+        if b == f64::NEG_INFINITY {
+            return a;
+        }
+        let max = a.max(b);
+        let diff = (a - b).abs();
+        match self {
+            MaxStar::LogMap => max + (-diff).exp().ln_1p(),
+            MaxStar::MaxLogMap => max,
+            MaxStar::Table { step, table } => {
+                max + table.get((diff / step) as usize).copied().unwrap_or(0.)
+            }
+        }
+    }
+}
+
+// BCJR (forward-backward MAP) 復号
+// 状態0から始まって状態0で終わるブロックについて、各情報ビットの事後 LLR (log P(1) / P(0)) を出す
+#[derive(Debug)]
+pub struct Bcjr {
+    pub trellis: Trellis,
+    pub sigma: f64,
+    pub max_star: MaxStar,
+    // 事前 LLR (繰り返し復号用、空なら0)
+    pub apriori: Vec<f64>,
+    pub llr: Vec<f64>,
+}
+
+impl Bcjr {
+    pub fn new(trellis: Trellis, sigma: f64, max_star: MaxStar) -> Self {
+        Bcjr {
+            trellis,
+            sigma,
+            max_star,
+            apriori: Vec::new(),
+            llr: Vec::new(),
+        }
+    }
+}
+
+// 通信路の LLR が要るので軟判定の入力だけ
+impl Decoder<f64> for Bcjr {
+    fn decode(&mut self, received: &[f64]) -> Vec<u8> {
+        let trellis = &self.trellis;
+        let len = received.len() / trellis.n;
+        let channel = 2. / (self.sigma * self.sigma);
+
+        // gamma[i][branch] = log P(受信 | 枝) + log P(入力) (定数は省く)
+        let gamma: Vec<Vec<f64>> = received
+            .chunks_exact(trellis.n)
+            .enumerate()
+            .map(|(i, received)| {
+                trellis.branches.iter().map(|branch| {
+                    let observed: f64 = received.iter()
+                        .enumerate()
+                        .map(|(j, r)| binary::bpsk(binary::bit(branch.output, trellis.n, j)) * r * channel / 2.)
+                        .sum();
+                    let prior: f64 = (0..trellis.k)
+                        .map(|b| {
+                            let apriori = self.apriori.get(i * trellis.k + b).copied().unwrap_or(0.);
+                            binary::bpsk(binary::bit(branch.input, trellis.k, b)) * apriori / 2.
+                        })
+                        .sum();
+                    observed + prior
+                }).collect()
+            })
+            .collect();
+
+        let mut alpha = vec![vec![f64::NEG_INFINITY; trellis.num_states]; len + 1];
+        alpha[0][0] = 0.;
+        for i in 0..len {
+            for (b, branch) in trellis.branches.iter().enumerate() {
+                let value = alpha[i][branch.from] + gamma[i][b];
+                alpha[i + 1][branch.to] = self.max_star.apply(alpha[i + 1][branch.to], value);
+            }
+        }
+
+        let mut beta = vec![vec![f64::NEG_INFINITY; trellis.num_states]; len + 1];
+        beta[len][0] = 0.;
+        for i in (0..len).rev() {
+            for (b, branch) in trellis.branches.iter().enumerate() {
+                let value = beta[i + 1][branch.to] + gamma[i][b];
+                beta[i][branch.from] = self.max_star.apply(beta[i][branch.from], value);
+            }
+        }
+
+        let mut llr = Vec::with_capacity(len * trellis.k);
+        for i in 0..len {
+            for bit in 0..trellis.k {
+                let mut one = f64::NEG_INFINITY;
+                let mut zero = f64::NEG_INFINITY;
+                for (b, branch) in trellis.branches.iter().enumerate() {
+                    let value = alpha[i][branch.from] + gamma[i][b] + beta[i + 1][branch.to];
+                    if binary::bit(branch.input, trellis.k, bit) == 1 {
+                        one = self.max_star.apply(one, value);
+                    } else {
+                        zero = self.max_star.apply(zero, value);
+                    }
+                }
+                llr.push(one - zero);
+            }
+        }
+        self.llr = llr;
+        self.llr.iter().map(|l| (*l > 0.) as u8).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::bpsk;
+    use crate::channel::{Awgn, Channel};
+    use crate::code::ConvolutionalCode;
+    use crate::source::Source;
+    use crate::viterbi::ViterbiSoft;
+
+    fn max_stars() -> Vec<MaxStar> {
+        vec![MaxStar::LogMap, MaxStar::MaxLogMap, MaxStar::table(0.5, 8)]
+    }
+
+    // 0で終端した len ビットの情報ビット
+    fn info(code: &ConvolutionalCode, len: usize, seed: u64) -> Vec<u8> {
+        let mut info = Source::new(seed).bits(len);
+        info.resize(len + code.termination_len() * code.k, 0);
+        info
+    }
+
+    #[test]
+    fn max_star_approximates_log_sum_exp() {
+        for (a, b) in [(0f64, 0f64), (1.5, -2.), (-3., -3.2), (10., 2.), (-0.7, 4.1)] {
+            let exact = (a.exp() + b.exp()).ln();
+            assert!((MaxStar::LogMap.apply(a, b) - exact).abs() < 1e-12);
+            let max_log = MaxStar::MaxLogMap.apply(a, b);
+            assert!(max_log <= exact && exact - max_log <= 2f64.ln() + 1e-12);
+            // 補正表の刻みの半分までずれる
+            assert!((MaxStar::table(0.5, 8).apply(a, b) - exact).abs() < 0.13);
+        }
+        assert_eq!(MaxStar::LogMap.apply(f64::NEG_INFINITY, 1.), 1.);
+    }
+
+    #[test]
+    fn decodes_noiseless_frame() {
+        let code = ConvolutionalCode::k3();
+        let info = info(&code, 100, 1);
+        let received: Vec<f64> = code.encode(&info).iter().map(|b| bpsk(*b)).collect();
+        for max_star in max_stars() {
+            let mut bcjr = Bcjr::new(Trellis::new(&code), 0.5, max_star.clone());
+            assert_eq!(bcjr.decode(&received), info, "{:?}", max_star);
+        }
+    }
+
+    #[test]
+    fn max_log_map_decides_like_viterbi() {
+        let code = ConvolutionalCode::k3();
+        let info = info(&code, 500, 2);
+        let received = Awgn::new(0.9, 3).transmit(&code.encode(&info));
+        let mut bcjr = Bcjr::new(Trellis::new(&code), 0.9, MaxStar::MaxLogMap);
+        let mut viterbi = ViterbiSoft::new(Trellis::new(&code));
+        let decided = bcjr.decode(&received);
+        assert_eq!(decided, Decoder::<f64>::decode(&mut viterbi, &received));
+        // 雑音で誤りが出ていること
+        assert_ne!(decided, info);
+    }
+}
@@ -0,0 +1,52 @@
+use self::super::Decoder;
+use crate::binary;
+use crate::metric::Metric;
+use crate::trellis::Trellis;
+
+// 今の状態から window シンボル先まで見て、一番距離が小さい経路の最初の入力を決める
+// 生き残り経路を持たない貪欲な探索なので、ビタビ復号ではない (DECODERS では "greedy")
+#[derive(Debug)]
+pub struct GreedySearch {
+    pub trellis: Trellis,
+    pub window: usize,
+}
+
+impl GreedySearch {
+    pub fn new(trellis: Trellis) -> Self {
+        // 拘束長
+        let window = (trellis.num_states as f64).log2() as usize / trellis.k + 1;
+        GreedySearch { trellis, window }
+    }
+
+    // state から received のシンボル数だけ進んだときの最小距離と最初の入力
+    fn search<T: Metric>(&self, state: usize, received: &[T]) -> (f64, usize) {
+        if received.is_empty() {
+            return (0., 0);
+        }
+        let n = self.trellis.n;
+        (0..self.trellis.num_inputs)
+            .map(|input| {
+                let branch = self.trellis.branch(state, input);
+                let diff = self.trellis.distance(branch, &received[..n]);
+                (diff + self.search(branch.to, &received[n..]).0, input)
+            })
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap()
+    }
+}
+
+impl<T: Metric> Decoder<T> for GreedySearch {
+    fn decode(&mut self, received: &[T]) -> Vec<u8> {
+        let n = self.trellis.n;
+        let len = received.len() / n;
+        let mut state = 0;
+        let mut answer = Vec::with_capacity(len * self.trellis.k);
+        for i in 0..len {
+            let end = (i + self.window).min(len);
+            let (_, input) = self.search(state, &received[i * n..end * n]);
+            state = self.trellis.branch(state, input).to;
+            answer.extend(binary::unpack(input, self.trellis.k));
+        }
+        answer
+    }
+}
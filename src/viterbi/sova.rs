@@ -0,0 +1,147 @@
+use self::super::Decoder;
+use crate::binary;
+use crate::trellis::Trellis;
+
+// 生き残りの枝と、負けた枝との距離の差
+#[derive(Debug, Copy, Clone)]
+struct Survivor {
+    parent: usize,
+    input: usize,
+    metric: f64,
+    // (負けた枝の parent, 負けた枝の input, 距離の差)
+    rival: Option<(usize, usize, f64)>,
+}
+
+// 軟出力ビタビ (SOVA)
+// 硬判定に加えて、各ビットの LLR (log P(1) / P(0)) を llr に入れる
+#[derive(Debug)]
+pub struct ViterbiSova {
+    pub trellis: Trellis,
+    pub sigma: f64,
+    pub llr: Vec<f64>,
+}
+
+impl ViterbiSova {
+    pub fn new(trellis: Trellis, sigma: f64) -> Self {
+        ViterbiSova {
+            trellis,
+            sigma,
+            llr: Vec::new(),
+        }
+    }
+}
+
+// 距離の差を LLR にするのにユークリッド距離の2乗を仮定しているので軟判定の入力だけ
+impl Decoder<f64> for ViterbiSova {
+    fn decode(&mut self, received: &[f64]) -> Vec<u8> {
+        let trellis = &self.trellis;
+        let len = received.len() / trellis.n;
+        let mut memo: Vec<Vec<Option<Survivor>>> = vec![vec![None; trellis.num_states]; len + 1];
+        memo[0][0] = Some(Survivor { parent: 0, input: 0, metric: 0., rival: None });
+        for (i, received) in received.chunks_exact(trellis.n).enumerate() {
+            for j in 0..trellis.num_states {
+                // 状態 j に入ってくる枝のうち一番距離が短いものと二番目に短いものを選ぶ
+                let mut candidates: Vec<(usize, usize, f64)> = trellis
+                    .incoming(j)
+                    .filter_map(|branch| {
+                        memo[i][branch.from].map(|s| {
+                            (branch.from, branch.input, s.metric + trellis.distance(branch, received))
+                        })
+                    })
+                    .collect();
+                candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+                if let Some(&(parent, input, metric)) = candidates.first() {
+                    let rival = candidates.get(1).map(|r| (r.0, r.1, r.2 - metric));
+                    memo[i + 1][j] = Some(Survivor { parent, input, metric, rival });
+                }
+            }
+        }
+
+        // 最尤パス
+        let mut path = vec![0; len + 1];
+        let mut inputs = vec![0; len];
+        for i in (1..=len).rev() {
+            let s = memo[i][path[i]].unwrap();
+            inputs[i - 1] = s.input;
+            path[i - 1] = s.parent;
+        }
+
+        // ユークリッド距離なら、距離の差 / (2 sigma^2) が LLR になる
+        let scale = 1. / (2. * self.sigma * self.sigma);
+        let mut reliability = vec![f64::INFINITY; len * trellis.k];
+        for i in (1..=len).rev() {
+            let (rival_parent, rival_input, delta) = match memo[i][path[i]].unwrap().rival {
+                Some(rival) => rival,
+                None => continue,
+            };
+            let delta = delta * scale;
+            // 負けたパスを最尤パスと合流するまでさかのぼって、違うビットの信頼度を更新する
+            let mut t = i - 1;
+            let mut state = rival_parent;
+            let mut input = rival_input;
+            loop {
+                let differ = inputs[t] ^ input;
+                for b in 0..trellis.k {
+                    if binary::bit(differ, trellis.k, b) == 1 {
+                        let r = &mut reliability[t * trellis.k + b];
+                        *r = r.min(delta);
+                    }
+                }
+                if state == path[t] || t == 0 {
+                    break;
+                }
+                let s = memo[t][state].unwrap();
+                state = s.parent;
+                input = s.input;
+                t -= 1;
+            }
+        }
+
+        let answer: Vec<u8> = inputs.iter()
+                                    .flat_map(|input| binary::unpack(*input, trellis.k))
+                                    .collect();
+        self.llr = answer.iter()
+                         .zip(&reliability)
+                         .map(|(b, r)| if *b == 1 { *r } else { -*r })
+                         .collect();
+        answer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::{Awgn, Channel};
+    use crate::code::ConvolutionalCode;
+    use crate::source::Source;
+    use crate::viterbi::{Bcjr, MaxStar};
+
+    #[test]
+    fn llr_is_close_to_log_map() {
+        let code = ConvolutionalCode::k3();
+        let trellis = Trellis::new(&code);
+        let sigma = 0.8;
+        let mut info = Source::new(1).bits(2000);
+        info.resize(2000 + code.termination_len(), 0);
+        let received = Awgn::new(sigma, 2).transmit(&code.encode(&info));
+
+        let mut sova = ViterbiSova::new(trellis.clone(), sigma);
+        let decided = sova.decode(&received);
+        let mut bcjr = Bcjr::new(trellis, sigma, MaxStar::LogMap);
+        bcjr.decode(&received);
+
+        // LLR の符号は硬判定と一致する
+        for (b, l) in decided.iter().zip(&sova.llr) {
+            assert_eq!(*b == 1, *l > 0.);
+        }
+        let agree = sova.llr.iter().zip(&bcjr.llr).filter(|(s, m)| (**s > 0.) == (**m > 0.)).count();
+        assert!(agree as f64 >= 0.99 * bcjr.llr.len() as f64);
+        // SOVA は信頼度を少し大きめに見積もる (この雑音で log-MAP より1割ほど大きい)
+        let finite: Vec<(f64, f64)> =
+            sova.llr.iter().zip(&bcjr.llr).filter(|(s, _)| s.is_finite()).map(|(s, m)| (*s, *m)).collect();
+        let sova_sum: f64 = finite.iter().map(|(s, _)| s.abs()).sum();
+        let map_sum: f64 = finite.iter().map(|(_, m)| m.abs()).sum();
+        let ratio = sova_sum / map_sum;
+        assert!((1.0..1.25).contains(&ratio), "{}", ratio);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use self::super::Decoder;
+use crate::binary;
+use crate::metric::Metric;
+use crate::trellis::Trellis;
+
+// よく使われる拘束長の5倍
+pub fn default_depth(trellis: &Trellis) -> usize {
+    5 * ((trellis.num_states as f64).log2() as usize / trellis.k + 1)
+}
+
+// 打ち切りトレースバックのビタビ復号
+// 受信信号を少しずつ受け取り、depth シンボル遅れで判定したビットを出していく
+// 終端を仮定しないので、連続したストリームをそのまま復号できる
+#[derive(Debug)]
+pub struct ViterbiStream<T: Metric> {
+    pub trellis: Trellis,
+    pub depth: usize,
+    // 各状態の今のユークリッド距離
+    metrics: Vec<f64>,
+    // 各時刻の [state] -> (parent state, input)
+    history: VecDeque<Vec<(usize, usize)>>,
+    // まだ1シンボル分たまっていない受信信号
+    pending: Vec<T>,
+}
+
+impl<T: Metric> ViterbiStream<T> {
+    pub fn new(trellis: Trellis, depth: usize) -> Self {
+        if depth == 0 {
+            panic!("traceback depth must be positive");
+        }
+        // 途中から受信し始めてもいいように、全状態を同じ距離から始める
+        let metrics = vec![0.; trellis.num_states];
+        ViterbiStream {
+            trellis,
+            depth,
+            metrics,
+            history: VecDeque::with_capacity(depth + 1),
+            pending: Vec::new(),
+        }
+    }
+
+    // 受信信号を入れて、判定が確定したビットを返す
+    pub fn push(&mut self, samples: &[T]) -> Vec<u8> {
+        self.pending.extend_from_slice(samples);
+        let n = self.trellis.n;
+        let symbols = self.pending.len() / n;
+        let mut decided = Vec::new();
+        for s in 0..symbols {
+            let received: Vec<T> = self.pending[s * n..(s + 1) * n].to_vec();
+            self.step(&received);
+            if self.history.len() > self.depth {
+                let input = self.traceback()[0];
+                decided.extend(binary::unpack(input, self.trellis.k));
+                self.history.pop_front();
+            }
+        }
+        self.pending.drain(..symbols * n);
+        decided
+    }
+
+    // 残りのビットを全部判定して返す
+    pub fn flush(&mut self) -> Vec<u8> {
+        let decided = self.traceback()
+                          .iter()
+                          .flat_map(|input| binary::unpack(*input, self.trellis.k))
+                          .collect();
+        self.history.clear();
+        self.pending.clear();
+        // 次のフレームが前のフレームの最後の状態に引っぱられないように、全状態を同じ距離に戻す
+        self.metrics.iter_mut().for_each(|m| *m = 0.);
+        decided
+    }
+
+    fn step(&mut self, received: &[T]) {
+        let mut next = vec![f64::INFINITY; self.trellis.num_states];
+        let mut survivors = vec![(0, 0); self.trellis.num_states];
+        for branch in &self.trellis.branches {
+            let metric = self.metrics[branch.from] + self.trellis.distance(branch, received);
+            if metric < next[branch.to] {
+                next[branch.to] = metric;
+                survivors[branch.to] = (branch.from, branch.input);
+            }
+        }
+        // 距離が増え続けないように一番小さいものを0にする
+        let min = next.iter().cloned().fold(f64::INFINITY, f64::min);
+        self.metrics = next.iter().map(|m| m - min).collect();
+        self.history.push_back(survivors);
+    }
+
+    // 今一番距離が小さい状態からさかのぼった、history の各時刻の入力
+    fn traceback(&self) -> Vec<usize> {
+        let mut state = (0..self.trellis.num_states)
+            .min_by(|a, b| self.metrics[*a].partial_cmp(&self.metrics[*b]).unwrap())
+            .unwrap();
+        let mut inputs: Vec<usize> = self.history
+                                         .iter()
+                                         .rev()
+                                         .map(|survivors| {
+                                             let (parent, input) = survivors[state];
+                                             state = parent;
+                                             input
+                                         })
+                                         .collect();
+        inputs.reverse();
+        inputs
+    }
+}
+
+// 1フレーム分を全部入れて復号する
+impl<T: Metric> Decoder<T> for ViterbiStream<T> {
+    fn decode(&mut self, received: &[T]) -> Vec<u8> {
+        let mut decided = self.push(received);
+        decided.extend(self.flush());
+        decided
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::{Awgn, Channel};
+    use crate::code::ConvolutionalCode;
+    use crate::source::Source;
+
+    #[test]
+    fn decoding_twice_gives_same_result() {
+        let code = ConvolutionalCode::new(3, &[0o7, 0o5]);
+        let trellis = Trellis::new(&code);
+        let info = Source::new(1).bits(200);
+        let received = Awgn::new(0.8, 2).transmit(&code.encode(&info));
+        let mut decoder: ViterbiStream<f64> = ViterbiStream::new(trellis.clone(), default_depth(&trellis));
+        let first = decoder.decode(&received);
+        let second = decoder.decode(&received);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn push_decides_depth_symbols_late() {
+        let code = ConvolutionalCode::k3();
+        let trellis = Trellis::new(&code);
+        let info = Source::new(1).bits(28);
+        let received: Vec<f64> = code.encode(&info).iter().map(|b| binary::bpsk(*b)).collect();
+        let mut decoder: ViterbiStream<f64> = ViterbiStream::new(trellis, 15);
+        // 半端なサンプル数で入れても、たまった分だけ進む
+        let mut decided = decoder.push(&received[..25]);
+        assert_eq!(decided.len(), 0);
+        decided.extend(decoder.push(&received[25..]));
+        assert_eq!(decided.len(), 28 - 15);
+        decided.extend(decoder.flush());
+        assert_eq!(decided, info);
+    }
+
+    #[test]
+    fn decodes_noiseless_stream_in_pieces() {
+        let code = ConvolutionalCode::nasa();
+        let trellis = Trellis::new(&code);
+        let info = Source::new(2).bits(500);
+        let received: Vec<f64> = code.encode(&info).iter().map(|b| binary::bpsk(*b)).collect();
+        let mut decoder: ViterbiStream<f64> = ViterbiStream::new(trellis.clone(), default_depth(&trellis));
+        let mut decided: Vec<u8> = received.chunks(37).flat_map(|chunk| decoder.push(chunk)).collect();
+        decided.extend(decoder.flush());
+        assert_eq!(decided, info);
+    }
+}
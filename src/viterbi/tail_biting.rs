@@ -0,0 +1,118 @@
+use self::super::Decoder;
+use crate::binary;
+use crate::metric::Metric;
+use crate::trellis::Trellis;
+
+// テイルバイティング符号の復号 (WAVA: wrap-around Viterbi algorithm)
+// 最初の状態がわからないので全状態を同じ距離から始め、
+// 最後の距離を次の周回の最初の距離にしてトレリスを何周かする
+// 最初と最後の状態が同じ経路 (テイルバイティング経路) が一番よくなったら終わる
+#[derive(Debug)]
+pub struct ViterbiTailBiting {
+    pub trellis: Trellis,
+    pub max_iterations: usize,
+}
+
+impl ViterbiTailBiting {
+    pub fn new(trellis: Trellis) -> Self {
+        ViterbiTailBiting {
+            trellis,
+            max_iterations: 4,
+        }
+    }
+}
+
+impl<T: Metric> Decoder<T> for ViterbiTailBiting {
+    fn decode(&mut self, received: &[T]) -> Vec<u8> {
+        let trellis = &self.trellis;
+        let len = received.len() / trellis.n;
+        let mut start = vec![0.; trellis.num_states];
+        // (この周の距離, 入力) 今までで一番いいテイルバイティング経路
+        let mut best_tail_biting: Option<(f64, Vec<usize>)> = None;
+        // 最後の周の一番いい経路 (テイルバイティングとは限らない)
+        let mut best_path = Vec::new();
+        let mut decided = None;
+
+        for _ in 0..self.max_iterations {
+            // metric, 経路の最初の状態
+            let mut metrics = start.clone();
+            let mut origins: Vec<usize> = (0..trellis.num_states).collect();
+            let mut memo: Vec<Vec<(usize, usize)>> = Vec::with_capacity(len);
+            for received in received.chunks_exact(trellis.n) {
+                let mut next = vec![f64::INFINITY; trellis.num_states];
+                let mut next_origins = vec![0; trellis.num_states];
+                let mut survivors = vec![(0, 0); trellis.num_states];
+                for branch in &trellis.branches {
+                    let metric = metrics[branch.from] + trellis.distance(branch, received);
+                    if metric < next[branch.to] {
+                        next[branch.to] = metric;
+                        next_origins[branch.to] = origins[branch.from];
+                        survivors[branch.to] = (branch.from, branch.input);
+                    }
+                }
+                metrics = next;
+                origins = next_origins;
+                memo.push(survivors);
+            }
+
+            let traceback = |end: usize| -> Vec<usize> {
+                let mut state = end;
+                let mut inputs: Vec<usize> = memo.iter()
+                                                 .rev()
+                                                 .map(|survivors| {
+                                                     let (parent, input) = survivors[state];
+                                                     state = parent;
+                                                     input
+                                                 })
+                                                 .collect();
+                inputs.reverse();
+                inputs
+            };
+            let best = (0..trellis.num_states)
+                .min_by(|a, b| metrics[*a].partial_cmp(&metrics[*b]).unwrap())
+                .unwrap();
+            if origins[best] == best {
+                decided = Some(traceback(best));
+                break;
+            }
+            best_path = traceback(best);
+
+            // 周回の最初の距離を引いて、この周の距離だけで比べる
+            let tail_biting = (0..trellis.num_states)
+                .filter(|s| origins[*s] == *s)
+                .map(|s| (metrics[s] - start[s], s))
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            if let Some((metric, end)) = tail_biting {
+                if best_tail_biting.as_ref().is_none_or(|b| metric < b.0) {
+                    best_tail_biting = Some((metric, traceback(end)));
+                }
+            }
+            let min = metrics.iter().cloned().fold(f64::INFINITY, f64::min);
+            start = metrics.iter().map(|m| m - min).collect();
+        }
+
+        let inputs = decided.or_else(|| best_tail_biting.map(|b| b.1))
+                            .unwrap_or(best_path);
+        inputs.iter()
+              .flat_map(|input| binary::unpack(*input, trellis.k))
+              .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::ConvolutionalCode;
+
+    #[test]
+    fn decodes_noiseless_frame_from_nonzero_state() {
+        for code in [ConvolutionalCode::k3(), ConvolutionalCode::nasa()] {
+            // 最後が1で終わるので、最初の状態は0でない
+            let info: Vec<u8> = (0..60).map(|i| ((i * 5 + 1) % 3 == 0) as u8).chain([1, 1]).collect();
+            assert_ne!(code.encode_from(0, &info).1, 0);
+            let received: Vec<f64> = code.encode_tail_biting(&info).iter().map(|b| binary::bpsk(*b)).collect();
+            let mut decoder = ViterbiTailBiting::new(Trellis::new(&code));
+            assert_eq!(decoder.decode(&received), info);
+        }
+    }
+}